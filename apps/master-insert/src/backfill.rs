@@ -0,0 +1,94 @@
+// backfill.rs
+use crate::cli::{BackfillArgs, Filters};
+use crate::stored::fetch_games;
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::error::Error;
+
+/// Computes the fingerprints of games imported before the column existed,
+/// one transaction per batch.
+///
+/// The unique index on `fingerprint` lets any number of NULLs through, so
+/// until this has run `import` only skips games that were imported after the
+/// migration. Games without a ply count cannot be decoded and stay without a
+/// fingerprint. A game with the fingerprint of another stored game is a
+/// duplicate: it is reported and left without one, as the index forbids it.
+pub async fn run(args: &BackfillArgs, pool: &PgPool) -> Result<(), Box<dyn Error>> {
+    let (mut after_id, mut filled, mut duplicates, mut skipped) = (0, 0, 0, 0);
+    loop {
+        let games =
+            fetch_games(pool, &Filters::default(), after_id, args.batch_size as i64).await?;
+        let Some(last) = games.last() else {
+            break;
+        };
+        after_id = last.id;
+
+        let mut seen = HashSet::new();
+        let mut ids: Vec<i32> = Vec::new();
+        let mut fingerprint_vec: Vec<Vec<u8>> = Vec::new();
+        for game in games.iter().filter(|game| game.fingerprint.is_none()) {
+            let Some(moves) = game.moves() else {
+                skipped += 1;
+                continue;
+            };
+            match moves.and_then(|moves| Ok(game.to_game(moves).fingerprint()?)) {
+                Ok(fingerprint) if seen.insert(fingerprint) => {
+                    ids.push(game.id);
+                    fingerprint_vec.push(fingerprint.to_bytes().to_vec());
+                }
+                Ok(_) => {
+                    println!("game {}: duplicate of another game", game.id);
+                    duplicates += 1;
+                }
+                Err(e) => {
+                    eprintln!("Skipping game {}: {}", game.id, e);
+                    skipped += 1;
+                }
+            }
+        }
+
+        if !args.dry_run && !ids.is_empty() {
+            let mut tx = pool.begin().await?;
+            // Earlier batches are committed, so this also catches duplicates of them
+            let updated: HashSet<i32> = sqlx::query_scalar!(
+                r#"
+                UPDATE games
+                SET fingerprint = t.fingerprint
+                FROM UNNEST($1::INTEGER[], $2::BYTEA[]) AS t(id, fingerprint)
+                WHERE games.id = t.id
+                    AND NOT EXISTS (SELECT 1 FROM games AS g WHERE g.fingerprint = t.fingerprint)
+                RETURNING games.id
+                "#,
+                &ids,
+                &fingerprint_vec,
+            )
+            .fetch_all(&mut *tx)
+            .await?
+            .into_iter()
+            .collect();
+            tx.commit().await?;
+
+            for id in ids.iter().filter(|id| !updated.contains(id)) {
+                println!("game {}: duplicate of another game", id);
+                duplicates += 1;
+            }
+            filled += updated.len();
+        } else {
+            filled += ids.len();
+        }
+        println!("Fingerprinted {} games", filled);
+    }
+
+    println!(
+        "{} {} games, {} duplicates left without one, {} without a ply count or failing to decode",
+        if args.dry_run {
+            "Would fingerprint"
+        } else {
+            "Fingerprinted"
+        },
+        filled,
+        duplicates,
+        skipped
+    );
+    Ok(())
+}
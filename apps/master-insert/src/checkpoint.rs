@@ -0,0 +1,197 @@
+// checkpoint.rs
+use pgn_reader::BufferedReader;
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
+
+/// SHA-256 of a whole file, which identifies it across renames and moves.
+pub fn file_checksum(path: &Path) -> io::Result<[u8; 32]> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1 << 20];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().into())
+}
+
+/// How far the committed batches of a file reach.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Where the first game not yet imported starts.
+    pub byte_offset: u64,
+    /// Games read up to the offset, including skipped ones.
+    pub games: u64,
+}
+
+/// Returns the furthest checkpoint recorded for a file, if any.
+pub async fn last_checkpoint(
+    pool: &PgPool,
+    checksum: &[u8; 32],
+) -> Result<Option<Checkpoint>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT MAX(byte_offset) AS byte_offset, SUM(games)::BIGINT AS games
+        FROM import_runs
+        WHERE checksum = $1
+        "#,
+        &checksum[..],
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(row.byte_offset.map(|byte_offset| Checkpoint {
+        byte_offset: byte_offset as u64,
+        games: row.games.unwrap_or(0) as u64,
+    }))
+}
+
+/// Forgets a file's checkpoints, before it is imported again from the start.
+pub async fn clear_checkpoints(pool: &PgPool, checksum: &[u8; 32]) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM import_runs WHERE checksum = $1", &checksum[..])
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// A batch of a file, recorded in the transaction that inserts its games.
+#[derive(Debug, Clone)]
+pub struct BatchRecord {
+    pub source_file: Arc<Path>,
+    pub checksum: [u8; 32],
+    /// Where the game after the batch starts.
+    pub byte_offset: u64,
+    /// Games read in the batch, including skipped ones.
+    pub games: usize,
+}
+
+impl BatchRecord {
+    pub async fn record(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        games_inserted: usize,
+    ) -> Result<(), sqlx::Error> {
+        let source_file = self.source_file.to_string_lossy();
+        sqlx::query!(
+            r#"
+            INSERT INTO import_runs (source_file, checksum, byte_offset, games, games_inserted)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            source_file.as_ref(),
+            &self.checksum[..],
+            self.byte_offset as i64,
+            self.games as i32,
+            games_inserted as i32,
+        )
+        .execute(&mut **tx)
+        .await?;
+        Ok(())
+    }
+}
+
+/// A reader that knows its position in the file it reads.
+pub struct OffsetReader {
+    file: File,
+    offset: u64,
+}
+
+impl OffsetReader {
+    /// Opens a file positioned at `offset`.
+    pub fn open(path: &Path, offset: u64) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        Ok(OffsetReader { file, offset })
+    }
+
+    /// Bytes read from the start of the file.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Moves back to `offset`, to re-read bytes a buffered reader held.
+    pub fn rewind_to(&mut self, offset: u64) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.offset = offset;
+        Ok(())
+    }
+}
+
+impl Read for OffsetReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.file.read(buf)?;
+        self.offset += read as u64;
+        Ok(read)
+    }
+}
+
+/// Takes the file back from a PGN reader, positioned at the first byte the
+/// reader had not consumed, so the next game is read from where the reader
+/// stopped.
+pub fn into_file(reader: BufferedReader<OffsetReader>) -> io::Result<OffsetReader> {
+    let (buffered, mut file) = reader.into_inner().into_inner();
+    let unread = buffered.get_ref().as_ref().len() as u64 - buffered.position();
+    file.rewind_to(file.offset() - unread)?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pgn_reader::{SanPlus, Skip, Visitor};
+
+    struct CountMoves(usize);
+
+    impl Visitor for CountMoves {
+        type Result = usize;
+
+        fn begin_game(&mut self) {
+            self.0 = 0;
+        }
+
+        fn san(&mut self, _san: SanPlus) {
+            self.0 += 1;
+        }
+
+        fn begin_variation(&mut self) -> Skip {
+            Skip(true)
+        }
+
+        fn end_game(&mut self) -> usize {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_resume_at_offset() {
+        let pgn = "[White \"a\"]\n\n1. e4 e5 *\n\n[White \"b\"]\n\n1. d4 *\n\n[White \"c\"]\n\n1. c4 c5 2. Nc3 *\n";
+        let path = std::env::temp_dir().join(format!("checkpoint-{}.pgn", std::process::id()));
+        std::fs::write(&path, pgn).unwrap();
+
+        let mut reader = BufferedReader::new(OffsetReader::open(&path, 0).unwrap());
+        let mut visitor = CountMoves(0);
+        assert_eq!(reader.read_game(&mut visitor).unwrap(), Some(2));
+        let file = into_file(reader).unwrap();
+        let offset = file.offset();
+
+        // The second game starts where the first one was consumed
+        assert!(pgn[offset as usize..]
+            .trim_start()
+            .starts_with("[White \"b\"]"));
+        let mut reader = BufferedReader::new(file);
+        assert_eq!(reader.read_game(&mut visitor).unwrap(), Some(1));
+        assert_eq!(reader.read_game(&mut visitor).unwrap(), Some(3));
+        assert_eq!(reader.read_game(&mut visitor).unwrap(), None);
+
+        assert_eq!(
+            file_checksum(&path).unwrap(),
+            <[u8; 32]>::from(Sha256::digest(pgn))
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,499 @@
+// cli.rs
+use crate::enums::ChessSpeed;
+use crate::Game;
+use chess_compression::CodecVersion;
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use sqlx::types::chrono::NaiveDate;
+use std::error::Error;
+use std::path::PathBuf;
+use std::thread;
+
+/// Parsed command line.
+#[derive(Debug)]
+pub struct Cli {
+    /// From `--database-url` or the `DATABASE_URL` environment variable.
+    pub database_url: Option<String>,
+    pub command: Subcommand,
+}
+
+#[derive(Debug)]
+pub enum Subcommand {
+    Import(ImportArgs),
+    Export(ExportArgs),
+    Verify(VerifyArgs),
+    Stats,
+    Reencode(ReencodeArgs),
+    Backfill(BackfillArgs),
+}
+
+#[derive(Debug)]
+pub struct ImportArgs {
+    /// Input files, with glob patterns expanded.
+    pub inputs: Vec<PathBuf>,
+    pub batch_size: usize,
+    /// Positions stored per game, from the position after the first move.
+    pub max_positions: usize,
+    /// Threads compressing games, besides the parser and the writer.
+    pub workers: usize,
+    pub dry_run: bool,
+    /// Read files from the start, ignoring their checkpoints.
+    pub restart: bool,
+    pub filters: Filters,
+}
+
+#[derive(Debug)]
+pub struct ExportArgs {
+    /// `None` writes to stdout.
+    pub output: Option<PathBuf>,
+    pub limit: Option<i64>,
+    pub filters: Filters,
+}
+
+#[derive(Debug)]
+pub struct VerifyArgs {
+    pub batch_size: usize,
+}
+
+#[derive(Debug)]
+pub struct ReencodeArgs {
+    pub to: CodecVersion,
+    pub batch_size: usize,
+    pub dry_run: bool,
+}
+
+#[derive(Debug)]
+pub struct BackfillArgs {
+    pub batch_size: usize,
+    pub dry_run: bool,
+}
+
+/// Which games a command reads. Every filter must match.
+#[derive(Debug, Clone, Default)]
+pub struct Filters {
+    /// Both players are rated at least this much.
+    pub min_elo: Option<i32>,
+    /// Empty matches every time control.
+    pub speeds: Vec<ChessSpeed>,
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+    pub eco_prefix: Option<String>,
+}
+
+impl Filters {
+    /// Whether a game passes the filters as it would be stored.
+    pub fn matches(&self, game: &Game) -> bool {
+        let speed = game.time_control.unwrap_or(ChessSpeed::Classical);
+        self.min_elo
+            .is_none_or(|elo| game.white_elo >= elo && game.black_elo >= elo)
+            && (self.speeds.is_empty() || self.speeds.contains(&speed))
+            && self.since.is_none_or(|since| game.date >= Some(since))
+            && self
+                .until
+                .is_none_or(|until| game.date.is_some_and(|date| date <= until))
+            && self
+                .eco_prefix
+                .as_deref()
+                .is_none_or(|prefix| game.eco.starts_with(prefix))
+    }
+
+    /// Time control names for SQL, matching the `chess_speed` labels.
+    pub fn speed_names(&self) -> Vec<String> {
+        self.speeds.iter().map(ChessSpeed::to_string).collect()
+    }
+}
+
+fn batch_size_arg() -> Arg {
+    Arg::new("batch-size")
+        .long("batch-size")
+        .value_name("GAMES")
+        .help("Games per database transaction")
+        .value_parser(value_parser!(u32).range(1..))
+        .default_value("5000")
+}
+
+fn dry_run_arg(help: &'static str) -> Arg {
+    Arg::new("dry-run")
+        .long("dry-run")
+        .help(help)
+        .action(ArgAction::SetTrue)
+}
+
+fn filter_args() -> [Arg; 5] {
+    [
+        Arg::new("min-elo")
+            .long("min-elo")
+            .value_name("ELO")
+            .help("Only games where both players are rated at least ELO")
+            .value_parser(value_parser!(i32)),
+        Arg::new("speed")
+            .long("speed")
+            .value_name("SPEED")
+            .help("Only games of this time control, such as blitz. Repeatable")
+            .value_parser(|value: &str| {
+                value
+                    .parse::<ChessSpeed>()
+                    .map_err(|_| format!("unknown time control {:?}", value))
+            })
+            .action(ArgAction::Append),
+        Arg::new("since")
+            .long("since")
+            .value_name("YYYY-MM-DD")
+            .help("Only games played on or after this date")
+            .value_parser(parse_date),
+        Arg::new("until")
+            .long("until")
+            .value_name("YYYY-MM-DD")
+            .help("Only games played on or before this date")
+            .value_parser(parse_date),
+        Arg::new("eco")
+            .long("eco")
+            .value_name("PREFIX")
+            .help("Only games whose ECO code starts with PREFIX, such as B2"),
+    ]
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|e| e.to_string())
+}
+
+fn parse_version(value: &str) -> Result<CodecVersion, String> {
+    let version = match value.to_ascii_lowercase().as_str() {
+        "v1" => CodecVersion::V1,
+        "v2" => CodecVersion::V2,
+        "v3" => CodecVersion::V3,
+        "v4" => CodecVersion::V4,
+        _ => {
+            return Err(format!(
+                "unknown codec version {:?}, expected v1 to v3",
+                value
+            ))
+        }
+    };
+    // Stored games must stay readable, so unfrozen formats are refused
+    if !version.is_frozen() {
+        return Err(format!(
+            "codec version {:?} is experimental and cannot be stored",
+            value
+        ));
+    }
+    Ok(version)
+}
+
+pub fn command() -> Command {
+    Command::new("master-import")
+        .about("Imports PGN files into the games database and maintains it")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .arg(
+            Arg::new("database-url")
+                .long("database-url")
+                .value_name("URL")
+                .help("PostgreSQL connection string")
+                .env("DATABASE_URL")
+                .global(true),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Import games from PGN files")
+                .arg(
+                    Arg::new("inputs")
+                        .value_name("INPUT")
+                        .help("PGN files, or glob patterns such as 'twic/*.pgn'")
+                        .required(true)
+                        .num_args(1..),
+                )
+                .arg(batch_size_arg())
+                .arg(
+                    Arg::new("max-positions")
+                        .long("max-positions")
+                        .value_name("PLIES")
+                        .help("Positions stored per game, from the first move")
+                        .value_parser(value_parser!(u16))
+                        .default_value("50"),
+                )
+                .arg(
+                    Arg::new("workers")
+                        .long("workers")
+                        .value_name("THREADS")
+                        .help("Threads compressing games, by default one per core not parsing or writing")
+                        .value_parser(value_parser!(u16).range(1..)),
+                )
+                .arg(dry_run_arg(
+                    "Read and compress games without writing to the database",
+                ))
+                .arg(
+                    Arg::new("restart")
+                        .long("restart")
+                        .help("Read files from the start instead of their last checkpoint")
+                        .action(ArgAction::SetTrue),
+                )
+                .args(filter_args()),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Write stored games as PGN")
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .value_name("FILE")
+                        .help("Output file, stdout by default")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("limit")
+                        .long("limit")
+                        .value_name("GAMES")
+                        .help("Export at most this many games")
+                        .value_parser(value_parser!(i64).range(0..)),
+                )
+                .args(filter_args()),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Check that stored games decode and match their fingerprints and positions")
+                .arg(batch_size_arg()),
+        )
+        .subcommand(Command::new("stats").about("Print counts of stored games and positions"))
+        .subcommand(
+            Command::new("reencode")
+                .about("Rewrite stored moves with another codec version")
+                .long_about(
+                    "Rewrite stored moves with another codec version.\n\n\
+                     Each game is decoded with the version in its codec_version \
+                     column, which is updated with the moves. Readers that do not \
+                     check the column, such as the web app, only decode v1.",
+                )
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .value_name("VERSION")
+                        .help("Codec version to store the moves with")
+                        .value_parser(parse_version)
+                        .required(true),
+                )
+                .arg(batch_size_arg())
+                .arg(dry_run_arg("Report the size change without writing")),
+        )
+        .subcommand(
+            Command::new("backfill")
+                .about("Compute the fingerprints of games imported before they were stored")
+                .long_about(
+                    "Compute the fingerprints of games imported before they were stored.\n\n\
+                     Import skips games by fingerprint, so until this has run it \
+                     only skips games imported after the fingerprint column was \
+                     added. Games without a ply count cannot be decoded and are \
+                     left out. Duplicates of a fingerprinted game are listed and \
+                     left without a fingerprint.",
+                )
+                .arg(batch_size_arg())
+                .arg(dry_run_arg("Count the games to fingerprint without writing")),
+        )
+}
+
+impl Cli {
+    /// Parses the process arguments, exiting with usage on error.
+    pub fn parse() -> Result<Self, Box<dyn Error>> {
+        Self::from_matches(&command().get_matches())
+    }
+
+    fn from_matches(matches: &ArgMatches) -> Result<Self, Box<dyn Error>> {
+        let (name, sub) = matches.subcommand().ok_or("A subcommand is required")?;
+        let command = match name {
+            "import" => Subcommand::Import(ImportArgs {
+                inputs: expand_inputs(sub.get_many::<String>("inputs").into_iter().flatten())?,
+                batch_size: batch_size(sub),
+                max_positions: usize::from(*sub.get_one::<u16>("max-positions").unwrap()),
+                workers: sub
+                    .get_one::<u16>("workers")
+                    .map_or_else(default_workers, |&workers| usize::from(workers)),
+                dry_run: sub.get_flag("dry-run"),
+                restart: sub.get_flag("restart"),
+                filters: filters(sub),
+            }),
+            "export" => Subcommand::Export(ExportArgs {
+                output: sub.get_one::<PathBuf>("output").cloned(),
+                limit: sub.get_one::<i64>("limit").copied(),
+                filters: filters(sub),
+            }),
+            "verify" => Subcommand::Verify(VerifyArgs {
+                batch_size: batch_size(sub),
+            }),
+            "stats" => Subcommand::Stats,
+            "reencode" => Subcommand::Reencode(ReencodeArgs {
+                to: *sub.get_one::<CodecVersion>("to").unwrap(),
+                batch_size: batch_size(sub),
+                dry_run: sub.get_flag("dry-run"),
+            }),
+            "backfill" => Subcommand::Backfill(BackfillArgs {
+                batch_size: batch_size(sub),
+                dry_run: sub.get_flag("dry-run"),
+            }),
+            _ => unreachable!("every subcommand is handled"),
+        };
+        Ok(Cli {
+            database_url: matches.get_one::<String>("database-url").cloned(),
+            command,
+        })
+    }
+
+    /// Returns the database URL, or an error naming the ways to set it.
+    pub fn require_database_url(&self) -> Result<&str, Box<dyn Error>> {
+        self.database_url
+            .as_deref()
+            .ok_or_else(|| "Set DATABASE_URL or pass --database-url".into())
+    }
+}
+
+// Arguments with defaults are always present
+fn batch_size(matches: &ArgMatches) -> usize {
+    *matches.get_one::<u32>("batch-size").unwrap() as usize
+}
+
+/// One worker per core, leaving one for the parser and one for the writer.
+fn default_workers() -> usize {
+    thread::available_parallelism()
+        .map_or(1, |cores| cores.get().saturating_sub(2))
+        .max(1)
+}
+
+fn filters(matches: &ArgMatches) -> Filters {
+    Filters {
+        min_elo: matches.get_one::<i32>("min-elo").copied(),
+        speeds: matches
+            .get_many::<ChessSpeed>("speed")
+            .into_iter()
+            .flatten()
+            .copied()
+            .collect(),
+        since: matches.get_one::<NaiveDate>("since").copied(),
+        until: matches.get_one::<NaiveDate>("until").copied(),
+        eco_prefix: matches.get_one::<String>("eco").cloned(),
+    }
+}
+
+/// Expands glob patterns into the files they match, sorted by path.
+///
+/// Inputs without wildcards are kept as given, so a missing file is reported
+/// when it is opened. A pattern matching no files is an error.
+pub fn expand_inputs<'a>(
+    patterns: impl IntoIterator<Item = &'a String>,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut inputs = Vec::new();
+    for pattern in patterns {
+        if !pattern.contains(['*', '?', '[']) {
+            inputs.push(PathBuf::from(pattern));
+            continue;
+        }
+        let matches = glob::glob(pattern)?.collect::<Result<Vec<_>, _>>()?;
+        if matches.is_empty() {
+            return Err(format!("No files match {}", pattern).into());
+        }
+        inputs.extend(matches);
+    }
+    Ok(inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_import() {
+        let matches = command().get_matches_from([
+            "master-import",
+            "import",
+            "a.pgn",
+            "b.pgn",
+            "--batch-size",
+            "100",
+            "--speed",
+            "blitz",
+            "--speed",
+            "Rapid",
+            "--since",
+            "2020-01-01",
+            "--dry-run",
+        ]);
+        let cli = Cli::from_matches(&matches).unwrap();
+        let Subcommand::Import(args) = cli.command else {
+            panic!("expected import");
+        };
+        assert_eq!(
+            args.inputs,
+            [PathBuf::from("a.pgn"), PathBuf::from("b.pgn")]
+        );
+        assert_eq!((args.batch_size, args.max_positions), (100, 50));
+        assert!(args.dry_run && !args.restart);
+        assert!(args.workers >= 1);
+        assert_eq!(args.filters.speeds, [ChessSpeed::Blitz, ChessSpeed::Rapid]);
+        assert_eq!(args.filters.since, NaiveDate::from_ymd_opt(2020, 1, 1));
+    }
+
+    #[test]
+    fn test_expand_inputs() {
+        let dir = std::env::temp_dir().join(format!("master-import-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["b.pgn", "a.pgn", "notes.txt"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let pattern = dir.join("*.pgn").to_str().unwrap().to_string();
+        let missing = dir.join("*.zip").to_str().unwrap().to_string();
+        let plain = "c.pgn".to_string();
+
+        let inputs = expand_inputs([&pattern, &plain]);
+        let error = expand_inputs([&missing]).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            inputs.unwrap(),
+            [dir.join("a.pgn"), dir.join("b.pgn"), PathBuf::from("c.pgn")]
+        );
+        assert_eq!(error.to_string(), format!("No files match {}", missing));
+    }
+
+    #[test]
+    fn test_reencode_refuses_unfrozen_versions() {
+        let matches = command().get_matches_from(["master-import", "reencode", "--to", "V3"]);
+        let cli = Cli::from_matches(&matches).unwrap();
+        let Subcommand::Reencode(args) = cli.command else {
+            panic!("expected reencode");
+        };
+        assert_eq!(args.to, CodecVersion::V3);
+
+        let result = command().try_get_matches_from(["master-import", "reencode", "--to", "v4"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_filters_match() {
+        let game = Game {
+            eco: "B22".to_string(),
+            date: NaiveDate::from_ymd_opt(2021, 6, 1),
+            white_elo: 2500,
+            black_elo: 2300,
+            time_control: Some(ChessSpeed::Blitz),
+            ..Default::default()
+        };
+        let filters = Filters {
+            min_elo: Some(2300),
+            speeds: vec![ChessSpeed::Blitz],
+            since: NaiveDate::from_ymd_opt(2021, 1, 1),
+            until: NaiveDate::from_ymd_opt(2021, 12, 31),
+            eco_prefix: Some("B2".to_string()),
+        };
+        assert!(filters.matches(&game));
+        assert!(!Filters {
+            min_elo: Some(2400),
+            ..filters.clone()
+        }
+        .matches(&game));
+        assert!(!Filters {
+            eco_prefix: Some("C".to_string()),
+            ..filters.clone()
+        }
+        .matches(&game));
+        // Games without a date fail date filters
+        assert!(!filters.matches(&Game { date: None, ..game }));
+    }
+}
@@ -1,6 +1,8 @@
 // Define an enum to represent the game result in PostgreSQL
 
+use chess_compression::GameResult;
 use std::fmt;
+use std::str::FromStr;
 #[derive(Debug, Copy, Default, Clone, PartialEq, Eq, sqlx::Type)]
 #[sqlx(type_name = "result", rename_all = "lowercase")]
 pub enum ResultType {
@@ -21,8 +23,18 @@ impl fmt::Display for ResultType {
     }
 }
 
+impl From<ResultType> for GameResult {
+    fn from(result: ResultType) -> Self {
+        match result {
+            ResultType::White => GameResult::WhiteWins,
+            ResultType::Black => GameResult::BlackWins,
+            ResultType::Draw => GameResult::Draw,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, sqlx::Type)]
-#[sqlx(type_name = "chess_speed", rename_all = "camelCase")] // Adjust based on PostgreSQL definition
+#[sqlx(type_name = "chess_speed", rename_all = "PascalCase")]
 pub enum ChessSpeed {
     UltraBullet,
     Bullet,
@@ -47,6 +59,15 @@ impl fmt::Display for ChessSpeed {
 }
 
 impl ChessSpeed {
+    pub const ALL: [ChessSpeed; 6] = [
+        ChessSpeed::UltraBullet,
+        ChessSpeed::Bullet,
+        ChessSpeed::Blitz,
+        ChessSpeed::Rapid,
+        ChessSpeed::Classical,
+        ChessSpeed::Correspondence,
+    ];
+
     pub fn from_seconds_and_increment(seconds: u64, increment: u64) -> ChessSpeed {
         let total = seconds + 40 * increment;
 
@@ -85,3 +106,15 @@ impl ChessSpeed {
         Ok(ChessSpeed::from_seconds_and_increment(seconds, increment))
     }
 }
+
+// Parses the names written by Display, ignoring case
+impl FromStr for ChessSpeed {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ChessSpeed::ALL
+            .into_iter()
+            .find(|speed| speed.to_string().eq_ignore_ascii_case(s))
+            .ok_or(())
+    }
+}
@@ -0,0 +1,137 @@
+// export.rs
+use crate::cli::ExportArgs;
+use crate::stored::fetch_games;
+use crate::Game;
+use chess_compression::GameResult;
+use sqlx::PgPool;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+const PAGE_SIZE: i64 = 5_000;
+
+/// Writes the games that pass the filters as PGN, in id order.
+///
+/// Progress goes to stderr, since the PGN may be written to stdout.
+pub async fn run(args: &ExportArgs, pool: &PgPool) -> Result<(), Box<dyn Error>> {
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+
+    let (mut after_id, mut exported, mut skipped) = (0, 0, 0);
+    loop {
+        let page_size = args
+            .limit
+            .map_or(PAGE_SIZE, |limit| (limit - exported).min(PAGE_SIZE));
+        if page_size <= 0 {
+            break;
+        }
+        let games = fetch_games(pool, &args.filters, after_id, page_size).await?;
+        let Some(last) = games.last() else {
+            break;
+        };
+        after_id = last.id;
+
+        for stored in &games {
+            match stored.moves() {
+                Some(Ok(moves)) => {
+                    write_pgn(&mut out, &stored.to_game(moves))?;
+                    exported += 1;
+                }
+                Some(Err(e)) => {
+                    eprintln!("Skipping game {}: {}", stored.id, e);
+                    skipped += 1;
+                }
+                // Without a ply count the end of the moves is unknown
+                None => skipped += 1,
+            }
+        }
+        eprintln!("Exported {} games", exported);
+    }
+    out.flush()?;
+
+    eprintln!(
+        "Export completed: {} games written, {} skipped",
+        exported, skipped
+    );
+    Ok(())
+}
+
+/// Writes a game with the seven tag roster, ratings and ECO code.
+fn write_pgn(out: &mut impl Write, game: &Game) -> io::Result<()> {
+    let result = GameResult::from(game.result);
+    let date = game.date.map_or("????.??.??".to_string(), |date| {
+        date.format("%Y.%m.%d").to_string()
+    });
+    writeln!(out, "[Event \"?\"]")?;
+    writeln!(out, "[Site \"?\"]")?;
+    writeln!(out, "[Date \"{}\"]", date)?;
+    writeln!(out, "[Round \"?\"]")?;
+    writeln!(out, "[White \"{}\"]", escape(&game.white_player))?;
+    writeln!(out, "[Black \"{}\"]", escape(&game.black_player))?;
+    writeln!(out, "[Result \"{}\"]", result)?;
+    // Missing ratings are stored as 0
+    if game.white_elo > 0 {
+        writeln!(out, "[WhiteElo \"{}\"]", game.white_elo)?;
+    }
+    if game.black_elo > 0 {
+        writeln!(out, "[BlackElo \"{}\"]", game.black_elo)?;
+    }
+    if !game.eco.is_empty() {
+        writeln!(out, "[ECO \"{}\"]", escape(&game.eco))?;
+    }
+    writeln!(out)?;
+
+    // Movetext lines are kept under 80 characters
+    let mut line = String::new();
+    let tokens = game.pgn_moves.iter().enumerate().flat_map(|(ply, san)| {
+        let number = ply.is_multiple_of(2).then(|| format!("{}.", ply / 2 + 1));
+        number.into_iter().chain([san.clone()])
+    });
+    for token in tokens.chain([result.to_string()]) {
+        if !line.is_empty() && line.len() + 1 + token.len() >= 80 {
+            writeln!(out, "{}", line)?;
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    writeln!(out, "{}", line)?;
+    writeln!(out)
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::ResultType;
+    use sqlx::types::chrono::NaiveDate;
+
+    #[test]
+    fn test_write_pgn() {
+        let game = Game {
+            eco: "C20".to_string(),
+            white_player: "Morphy, Paul".to_string(),
+            black_player: "Duke \"Karl\"".to_string(),
+            date: NaiveDate::from_ymd_opt(1858, 11, 2),
+            result: ResultType::White,
+            pgn_moves: ["e4", "e5", "Nf3"].map(String::from).to_vec(),
+            white_elo: 2690,
+            black_elo: 0,
+            time_control: None,
+        };
+        let mut out = Vec::new();
+        write_pgn(&mut out, &game).unwrap();
+        let pgn = String::from_utf8(out).unwrap();
+        assert!(pgn.contains("[Date \"1858.11.02\"]\n"));
+        assert!(pgn.contains("[Black \"Duke \\\"Karl\\\"\"]\n"));
+        assert!(pgn.contains("[WhiteElo \"2690\"]\n[ECO \"C20\"]\n\n"));
+        assert!(pgn.ends_with("\n1. e4 e5 2. Nf3 1-0\n\n"));
+    }
+}
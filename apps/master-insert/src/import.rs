@@ -0,0 +1,532 @@
+// import.rs
+use crate::checkpoint::{
+    clear_checkpoints, file_checksum, into_file, last_checkpoint, BatchRecord, Checkpoint,
+    OffsetReader,
+};
+use crate::cli::{Filters, ImportArgs};
+use crate::enums::*;
+use crate::pipeline::{PipelineStats, StageStats};
+use crate::stored::codec_version_number;
+use crate::{Game, IMPORT_CODEC_VERSION};
+use chess_compression::{GameFingerprint, Importer, SampledPosition};
+use pgn_reader::BufferedReader;
+use sqlx::types::chrono::NaiveDate;
+use sqlx::PgPool;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::io;
+use std::path::Path;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+use tokio::sync::{mpsc, OwnedSemaphorePermit, Semaphore};
+use tokio::task::spawn_blocking;
+
+/// Batches in flight per compression worker, counting the one it compresses.
+const BATCHES_PER_WORKER: usize = 2;
+
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A file to import and where to start reading it.
+struct Source {
+    path: Arc<Path>,
+    checksum: [u8; 32],
+    checkpoint: Checkpoint,
+}
+
+/// A batch moving through the pipeline.
+///
+/// The permit holds the batch's place in the window of batches in flight and
+/// is released when the writer drops the batch, which is what keeps the
+/// parser from running ahead of the database.
+struct InFlight<T> {
+    seq: u64,
+    record: BatchRecord,
+    data: T,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl<T> InFlight<T> {
+    fn map<U>(self, f: impl FnOnce(T) -> U) -> InFlight<U> {
+        InFlight {
+            seq: self.seq,
+            record: self.record,
+            data: f(self.data),
+            _permit: self._permit,
+        }
+    }
+}
+
+/// Imports the games of every input file.
+///
+/// The import is a pipeline: a parser thread reads batches of games, a pool
+/// of workers compresses them and extracts their positions, and an async
+/// writer inserts them. Stages are connected by bounded channels and at most
+/// `workers * BATCHES_PER_WORKER` batches are in flight, so a slow stage
+/// slows the ones before it instead of filling memory. Throughput per stage
+/// is printed every `REPORT_INTERVAL`.
+///
+/// Each batch is committed together with a checkpoint in `import_runs`, keyed
+/// by the file's checksum, and a later run resumes each file from its last
+/// checkpoint. Batches are idempotent on their own as well: games already in
+/// the table are skipped by fingerprint, and only new games get positions.
+/// Games imported before fingerprints were stored are only skipped once
+/// `backfill` has computed theirs.
+///
+/// Without a pool this is a dry run: games are read, filtered and compressed,
+/// but nothing is written and no checkpoint is read.
+pub async fn run(args: &ImportArgs, pool: Option<&PgPool>) -> Result<(), Box<dyn Error>> {
+    println!("Starting the importer with {} workers...", args.workers);
+    let sources = plan_sources(args, pool).await?;
+
+    let parse_stats = Arc::new(StageStats::new("parse", 1));
+    let compress_stats = Arc::new(StageStats::new("compress", args.workers));
+    let write_stats = Arc::new(StageStats::new("write", 1));
+    let stats = Arc::new(PipelineStats::new(&[
+        &parse_stats,
+        &compress_stats,
+        &write_stats,
+    ]));
+    let reporter = stats.spawn_reporter(REPORT_INTERVAL);
+
+    // Channels hold the whole window, so only the permits ever block the parser
+    let window = args.workers * BATCHES_PER_WORKER;
+    let permits = Arc::new(Semaphore::new(window));
+    let (parsed_tx, parsed_rx) = sync_channel(window);
+    let (compressed_tx, compressed_rx) = mpsc::channel(window);
+
+    let parser = spawn_blocking({
+        let (filters, batch_size) = (args.filters.clone(), args.batch_size);
+        let permits = Arc::clone(&permits);
+        move || {
+            read_batches(
+                sources,
+                &filters,
+                batch_size,
+                &permits,
+                &parsed_tx,
+                &parse_stats,
+            )
+        }
+    });
+    let parsed_rx = Arc::new(Mutex::new(parsed_rx));
+    let workers: Vec<_> = (0..args.workers)
+        .map(|_| {
+            let (parsed_rx, compressed_tx) = (Arc::clone(&parsed_rx), compressed_tx.clone());
+            let (max_positions, stats) = (args.max_positions, Arc::clone(&compress_stats));
+            spawn_blocking(move || {
+                compress_batches(&parsed_rx, &compressed_tx, max_positions, &stats)
+            })
+        })
+        .collect();
+    drop(compressed_tx);
+
+    let written = write_batches(compressed_rx, pool, &write_stats).await;
+    // After a failed write the writer has hung up, and closing the window
+    // stops a parser waiting for room
+    permits.close();
+    let parsed = parser.await?;
+    for worker in workers {
+        worker.await?;
+    }
+    reporter.abort();
+    written?;
+    parsed?;
+    stats.print_summary();
+
+    if pool.is_none() {
+        println!("Dry run completed, nothing was written.");
+    } else {
+        println!("Import completed successfully.");
+    }
+    Ok(())
+}
+
+/// Checksums the input files and looks up where each one resumes.
+async fn plan_sources(
+    args: &ImportArgs,
+    pool: Option<&PgPool>,
+) -> Result<Vec<Source>, Box<dyn Error>> {
+    let mut sources = Vec::with_capacity(args.inputs.len());
+    for path in &args.inputs {
+        let path: Arc<Path> = Arc::from(path.as_path());
+        // Reads the whole file
+        let checksum = spawn_blocking({
+            let path = Arc::clone(&path);
+            move || file_checksum(&path)
+        })
+        .await??;
+        let checkpoint = match pool {
+            Some(pool) if args.restart => {
+                clear_checkpoints(pool, &checksum).await?;
+                Checkpoint::default()
+            }
+            Some(pool) => last_checkpoint(pool, &checksum).await?.unwrap_or_default(),
+            None => Checkpoint::default(),
+        };
+        sources.push(Source {
+            path,
+            checksum,
+            checkpoint,
+        });
+    }
+    Ok(sources)
+}
+
+/// Parser stage: reads the sources in order into batches of up to
+/// `batch_size` games that pass the filters.
+///
+/// Returns early without an error if the later stages stopped, since the
+/// writer reports why.
+fn read_batches(
+    sources: Vec<Source>,
+    filters: &Filters,
+    batch_size: usize,
+    permits: &Arc<Semaphore>,
+    parsed_tx: &SyncSender<InFlight<Vec<Game>>>,
+    stats: &StageStats,
+) -> io::Result<()> {
+    let handle = Handle::current();
+    let mut seq = 0;
+    for source in sources {
+        let checkpoint = source.checkpoint;
+        if checkpoint.byte_offset > 0 {
+            println!(
+                "Resuming {} at byte {}, after {} games",
+                source.path.display(),
+                checkpoint.byte_offset,
+                checkpoint.games
+            );
+        } else {
+            println!("Reading {}", source.path.display());
+        }
+
+        let mut file = OffsetReader::open(&source.path, checkpoint.byte_offset)?;
+        let mut importer = Importer::new();
+        let mut read_games = checkpoint.games;
+        loop {
+            let Ok(permit) = handle.block_on(Arc::clone(permits).acquire_owned()) else {
+                return Ok(());
+            };
+            let started = Instant::now();
+            // A fresh reader per batch, so the offset after the batch is exact
+            let mut reader = BufferedReader::new(file);
+            let mut games_batch = Vec::with_capacity(batch_size);
+            let mut batch_games = 0;
+            let finished = loop {
+                let Some(game) = reader.read_game(&mut importer)? else {
+                    break true;
+                };
+                read_games += 1;
+                batch_games += 1;
+                // Unfinished games, games without players and filtered out games are skipped
+                if let Ok(game) = game {
+                    let game = Game::from(game);
+                    if filters.matches(&game) {
+                        games_batch.push(game);
+                    }
+                }
+                if games_batch.len() >= batch_size {
+                    break false;
+                }
+            };
+            file = into_file(reader)?;
+            stats.record(batch_games, started);
+
+            if batch_games == 0 {
+                if checkpoint.byte_offset > 0 && read_games == checkpoint.games {
+                    println!("{} was already imported", source.path.display());
+                }
+                break;
+            }
+            // Batches whose games were all filtered out are recorded too, so a
+            // resumed import does not read them again
+            let batch = InFlight {
+                seq,
+                record: BatchRecord {
+                    source_file: Arc::clone(&source.path),
+                    checksum: source.checksum,
+                    byte_offset: file.offset(),
+                    games: batch_games,
+                },
+                data: games_batch,
+                _permit: permit,
+            };
+            seq += 1;
+            if parsed_tx.send(batch).is_err() || finished {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Compression stage: compresses the moves and extracts the positions of
+/// batches until the parser is done or the writer has stopped.
+fn compress_batches(
+    parsed_rx: &Mutex<Receiver<InFlight<Vec<Game>>>>,
+    compressed_tx: &mpsc::Sender<InFlight<PreparedBatch>>,
+    max_positions: usize,
+    stats: &StageStats,
+) {
+    loop {
+        // Idle workers take turns waiting on the channel
+        let Ok(batch) = parsed_rx.lock().unwrap().recv() else {
+            return;
+        };
+        let started = Instant::now();
+        let batch = batch.map(|games| PreparedBatch::new(&games, max_positions));
+        stats.record(batch.data.games, started);
+        if compressed_tx.blocking_send(batch).is_err() {
+            return;
+        }
+    }
+}
+
+/// Writer stage: inserts batches in the order the parser read them.
+///
+/// Workers finish batches out of order, but a checkpoint is only valid once
+/// every batch before it is committed, so later batches wait for earlier ones.
+async fn write_batches(
+    mut compressed_rx: mpsc::Receiver<InFlight<PreparedBatch>>,
+    pool: Option<&PgPool>,
+    stats: &StageStats,
+) -> Result<(), Box<dyn Error>> {
+    let mut pending = BTreeMap::new();
+    let mut next_seq = 0;
+    // Every game read, and the ones inserted or, in a dry run, compressed
+    let (mut games_read, mut games_stored) = (0, 0);
+    while let Some(batch) = compressed_rx.recv().await {
+        pending.insert(batch.seq, batch);
+        while let Some(batch) = pending.remove(&next_seq) {
+            next_seq += 1;
+            let started = Instant::now();
+            let games = batch.data.games;
+            games_stored += match pool {
+                Some(pool) => batch.data.insert(pool, &batch.record).await?,
+                None => batch.data.len(),
+            };
+            stats.record(games, started);
+            games_read += batch.record.games;
+            println!(
+                "Processed {} games: {} {}, {} skipped as filtered, invalid or duplicate",
+                games_read,
+                games_stored,
+                if pool.is_some() {
+                    "inserted"
+                } else {
+                    "compressed"
+                },
+                games_read - games_stored
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Columns of the games of a batch that compressed, without duplicates.
+struct PreparedBatch {
+    // Games the parser passed on, including the ones dropped here. Games the
+    // parser skipped or filtered out are only counted in the batch record.
+    games: usize,
+    eco_vec: Vec<String>,
+    white_player_vec: Vec<String>,
+    black_player_vec: Vec<String>,
+    date_vec: Vec<Option<NaiveDate>>,
+    result_vec: Vec<ResultType>,
+    white_elo_vec: Vec<i32>,
+    black_elo_vec: Vec<i32>,
+    time_control_vec: Vec<ChessSpeed>,
+    compressed_pgn_vec: Vec<Vec<u8>>,
+    plies_vec: Vec<i16>,
+    fingerprint_vec: Vec<Vec<u8>>,
+    // Positions of the games that made it into the insert, keyed by fingerprint
+    positions: HashMap<[u8; 16], Vec<SampledPosition>>,
+}
+
+impl PreparedBatch {
+    fn new(games_batch: &[Game], max_positions: usize) -> Self {
+        let capacity = games_batch.len();
+        let mut batch = PreparedBatch {
+            games: capacity,
+            eco_vec: Vec::with_capacity(capacity),
+            white_player_vec: Vec::with_capacity(capacity),
+            black_player_vec: Vec::with_capacity(capacity),
+            date_vec: Vec::with_capacity(capacity),
+            result_vec: Vec::with_capacity(capacity),
+            white_elo_vec: Vec::with_capacity(capacity),
+            black_elo_vec: Vec::with_capacity(capacity),
+            time_control_vec: Vec::with_capacity(capacity),
+            compressed_pgn_vec: Vec::with_capacity(capacity),
+            plies_vec: Vec::with_capacity(capacity),
+            fingerprint_vec: Vec::with_capacity(capacity),
+            positions: HashMap::with_capacity(capacity),
+        };
+        let mut seen: HashSet<GameFingerprint> = HashSet::with_capacity(capacity);
+
+        for game in games_batch {
+            let (transcoded, fingerprint) =
+                match (game.transcode(max_positions), game.fingerprint()) {
+                    (Ok(transcoded), Ok(fingerprint)) => (transcoded, fingerprint),
+                    (Err(e), _) | (_, Err(e)) => {
+                        eprintln!("Error compressing game: {}", e);
+                        continue; // Here, we choose to skip the game on compression error
+                    }
+                };
+            // The column is a SMALLINT, and no real game comes close to its limit
+            let Ok(plies) = i16::try_from(game.pgn_moves.len()) else {
+                eprintln!("Skipping game with {} plies", game.pgn_moves.len());
+                continue;
+            };
+
+            // Skip duplicates within the batch, the unique index handles the rest
+            if !seen.insert(fingerprint) {
+                continue;
+            }
+            batch.compressed_pgn_vec.push(transcoded.compressed);
+            batch.plies_vec.push(plies);
+            batch.fingerprint_vec.push(fingerprint.to_bytes().to_vec());
+            batch
+                .positions
+                .insert(fingerprint.to_bytes(), transcoded.positions);
+
+            batch.eco_vec.push(game.eco.clone());
+            batch.white_player_vec.push(game.white_player.clone());
+            batch.black_player_vec.push(game.black_player.clone());
+            batch.date_vec.push(game.date);
+            batch.result_vec.push(game.result);
+            batch.white_elo_vec.push(game.white_elo);
+            batch.black_elo_vec.push(game.black_elo);
+            batch
+                .time_control_vec
+                .push(game.time_control.unwrap_or(ChessSpeed::Classical));
+        }
+        batch
+    }
+
+    /// Games left to insert, without the invalid ones and duplicates.
+    fn len(&self) -> usize {
+        self.fingerprint_vec.len()
+    }
+
+    /// Inserts the games, their positions and the batch's checkpoint in one
+    /// transaction, and returns the number of games inserted.
+    async fn insert(
+        mut self,
+        pool: &PgPool,
+        record: &BatchRecord,
+    ) -> Result<usize, Box<dyn Error>> {
+        // Start a new transaction
+        let mut tx = pool.begin().await?;
+
+        // Bulk insert into the 'games' table and retrieve the generated ids. Games
+        // already in the table are skipped and return no row.
+        let inserted_games: Vec<(i32, Vec<SampledPosition>)> = sqlx::query!(
+            r#"
+            INSERT INTO games (
+                eco, white_player, black_player, date, result, white_elo, black_elo, time_control, pgn_moves, plies, codec_version, fingerprint
+            )
+            SELECT
+                t.eco,
+                t.white_player,
+                t.black_player,
+                t.date,
+                t.result::result,
+                t.white_elo,
+                t.black_elo,
+                t.time_control::chess_speed,
+                t.pgn_moves,
+                t.plies,
+                $12::SMALLINT,
+                t.fingerprint
+            FROM UNNEST(
+                $1::VARCHAR[],
+                $2::VARCHAR[],
+                $3::VARCHAR[],
+                $4::DATE[],
+                $5::VARCHAR[],
+                $6::INTEGER[],
+                $7::INTEGER[],
+                $8::VARCHAR[],
+                $9::BYTEA[],
+                $10::SMALLINT[],
+                $11::BYTEA[]
+            ) AS t(eco, white_player, black_player, date, result, white_elo, black_elo, time_control, pgn_moves, plies, fingerprint)
+            ON CONFLICT (fingerprint) DO NOTHING
+            RETURNING id, fingerprint
+            "#,
+            &self.eco_vec,
+            &self.white_player_vec,
+            &self.black_player_vec,
+            &self.date_vec as &[Option<NaiveDate>],
+            &self.result_vec.iter().map(|r| r.to_string()).collect::<Vec<_>>(),
+            &self.white_elo_vec,
+            &self.black_elo_vec,
+            &self
+                .time_control_vec
+                .iter()
+                .map(|tc| tc.to_string())
+                .collect::<Vec<_>>(),
+            &self.compressed_pgn_vec,
+            &self.plies_vec,
+            &self.fingerprint_vec,
+            codec_version_number(IMPORT_CODEC_VERSION),
+        )
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .map(|row| -> Result<(i32, Vec<SampledPosition>), Box<dyn Error>> {
+            let positions = row
+                .fingerprint
+                .as_deref()
+                .and_then(|fingerprint| <[u8; 16]>::try_from(fingerprint).ok())
+                .and_then(|fingerprint| self.positions.remove(&fingerprint))
+                .ok_or("Inserted game returned an unknown fingerprint")?;
+            Ok((row.id, positions))
+        })
+        .collect::<Result<_, _>>()?;
+
+        // Prepare vectors for bulk inserting into the 'positions' table
+        let mut position_game_ids: Vec<i32> = Vec::new();
+        let mut move_numbers: Vec<i16> = Vec::new();
+        let mut positions_vec: Vec<Vec<u8>> = Vec::new();
+
+        // Positions were compressed along with the moves
+        for (game_id, positions) in &inserted_games {
+            for sampled in positions {
+                position_game_ids.push(*game_id);
+                move_numbers.push(sampled.ply as i16);
+                positions_vec.push(sampled.position.to_vec());
+            }
+        }
+
+        // Bulk insert into the 'positions' table
+        if !position_game_ids.is_empty() {
+            sqlx::query!(
+                r#"
+                INSERT INTO positions (game_id, move_number, position)
+                SELECT * FROM UNNEST(
+                    $1::INTEGER[],
+                    $2::SMALLINT[],
+                    $3::BYTEA[]
+                ) AS t(game_id, move_number, position)
+                "#,
+                &position_game_ids,
+                &move_numbers,
+                &positions_vec,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        // The checkpoint commits with the games, so a crash loses both or neither
+        let games_inserted = inserted_games.len();
+        record.record(&mut tx, games_inserted).await?;
+
+        // Commit the transaction
+        tx.commit().await?;
+
+        Ok(games_inserted)
+    }
+}
@@ -0,0 +1,128 @@
+// pipeline.rs
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+/// Games passed through a pipeline stage and the time its threads spent
+/// working on them, excluding time blocked on the other stages.
+#[derive(Debug)]
+pub struct StageStats {
+    name: &'static str,
+    threads: usize,
+    games: AtomicU64,
+    busy_nanos: AtomicU64,
+}
+
+impl StageStats {
+    pub fn new(name: &'static str, threads: usize) -> Self {
+        StageStats {
+            name,
+            threads,
+            games: AtomicU64::new(0),
+            busy_nanos: AtomicU64::new(0),
+        }
+    }
+
+    /// Adds `games` handled since `started`.
+    pub fn record(&self, games: usize, started: Instant) {
+        let busy = started.elapsed().as_nanos() as u64;
+        self.games.fetch_add(games as u64, Ordering::Relaxed);
+        self.busy_nanos.fetch_add(busy, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> (u64, u64) {
+        (
+            self.games.load(Ordering::Relaxed),
+            self.busy_nanos.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Throughput of the stages of a pipeline, reported periodically.
+///
+/// A stage near 100% busy is the bottleneck, the stages after it wait on it
+/// and the ones before it wait on backpressure.
+#[derive(Debug)]
+pub struct PipelineStats {
+    stages: Vec<Arc<StageStats>>,
+    started: Instant,
+}
+
+impl PipelineStats {
+    pub fn new(stages: &[&Arc<StageStats>]) -> Self {
+        PipelineStats {
+            stages: stages.iter().map(|&stage| Arc::clone(stage)).collect(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Prints the throughput of every stage since the last report, every
+    /// `interval`, until the returned task is aborted.
+    pub fn spawn_reporter(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let stats = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            // The first tick completes immediately
+            ticks.tick().await;
+            let mut last = stats.snapshots();
+            loop {
+                ticks.tick().await;
+                let current = stats.snapshots();
+                println!("{}", stats.format(&last, &current, interval));
+                last = current;
+            }
+        })
+    }
+
+    /// Prints the average throughput of every stage since the start.
+    pub fn print_summary(&self) {
+        let start = vec![(0, 0); self.stages.len()];
+        println!(
+            "Average {}",
+            self.format(&start, &self.snapshots(), self.started.elapsed())
+        );
+    }
+
+    fn snapshots(&self) -> Vec<(u64, u64)> {
+        self.stages.iter().map(|stage| stage.snapshot()).collect()
+    }
+
+    fn format(&self, from: &[(u64, u64)], to: &[(u64, u64)], elapsed: Duration) -> String {
+        let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+        let stages: Vec<String> = self
+            .stages
+            .iter()
+            .zip(from.iter().zip(to))
+            .map(|(stage, ((games, busy), (games_to, busy_to)))| {
+                let rate = (games_to - games) as f64 / seconds;
+                let busy = (busy_to - busy) as f64 / 1e9 / (seconds * stage.threads as f64);
+                format!(
+                    "{} {:.0} games/s ({:.0}% busy)",
+                    stage.name,
+                    rate,
+                    busy.min(1.0) * 100.0
+                )
+            })
+            .collect();
+        stages.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let parse = Arc::new(StageStats::new("parse", 1));
+        let compress = Arc::new(StageStats::new("compress", 4));
+        let stats = PipelineStats::new(&[&parse, &compress]);
+        let from = [(1_000, 0), (0, 0)];
+        let to = [(3_000, 1_000_000_000), (2_000, 2_000_000_000)];
+        assert_eq!(
+            stats.format(&from, &to, Duration::from_secs(2)),
+            "parse 1000 games/s (50% busy), compress 1000 games/s (25% busy)"
+        );
+    }
+}
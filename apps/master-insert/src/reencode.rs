@@ -0,0 +1,97 @@
+// reencode.rs
+use crate::cli::{Filters, ReencodeArgs};
+use crate::stored::{codec_version_number, fetch_games};
+use chess_compression::{compress_pgn_with_version, decompress_pgn_with_version};
+use sqlx::PgPool;
+use std::error::Error;
+
+/// Rewrites the stored moves of every game with a ply count in another codec
+/// version, and records it in `codec_version`, one transaction per batch.
+///
+/// Each game is decoded with its stored version and checked to decode back to
+/// the same moves with the new one before it is written. Games already in the
+/// new version are left alone, so an interrupted run can be started again.
+pub async fn run(args: &ReencodeArgs, pool: &PgPool) -> Result<(), Box<dyn Error>> {
+    let to_number = codec_version_number(args.to);
+    let (mut after_id, mut reencoded, mut unchanged, mut skipped) = (0, 0, 0, 0);
+    let (mut old_bytes, mut new_bytes) = (0, 0);
+    loop {
+        let games =
+            fetch_games(pool, &Filters::default(), after_id, args.batch_size as i64).await?;
+        let Some(last) = games.last() else {
+            break;
+        };
+        after_id = last.id;
+
+        let mut ids: Vec<i32> = Vec::with_capacity(games.len());
+        let mut compressed_pgn_vec: Vec<Vec<u8>> = Vec::with_capacity(games.len());
+        for game in &games {
+            if game.codec_version == to_number {
+                unchanged += 1;
+                continue;
+            }
+            let Some(moves) = game.moves() else {
+                skipped += 1;
+                continue;
+            };
+            let compressed = moves.and_then(|moves| {
+                let compressed = compress_pgn_with_version(&moves, args.to)?;
+                let plies = moves.len();
+                let decoded = decompress_pgn_with_version(&compressed, plies, args.to)?;
+                Ok((decoded == moves).then_some(compressed))
+            });
+            match compressed {
+                Ok(Some(compressed)) => {
+                    old_bytes += game.pgn_moves.len();
+                    new_bytes += compressed.len();
+                    ids.push(game.id);
+                    compressed_pgn_vec.push(compressed);
+                }
+                Ok(None) => {
+                    eprintln!("Skipping game {}: moves changed when reencoded", game.id);
+                    skipped += 1;
+                }
+                Err(e) => {
+                    eprintln!("Skipping game {}: {}", game.id, e);
+                    skipped += 1;
+                }
+            }
+        }
+
+        if !args.dry_run && !ids.is_empty() {
+            let mut tx = pool.begin().await?;
+            sqlx::query!(
+                r#"
+                UPDATE games
+                SET pgn_moves = t.pgn_moves, codec_version = $3
+                FROM UNNEST($1::INTEGER[], $2::BYTEA[]) AS t(id, pgn_moves)
+                WHERE games.id = t.id
+                "#,
+                &ids,
+                &compressed_pgn_vec,
+                to_number,
+            )
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+        }
+        reencoded += ids.len();
+        println!("Reencoded {} games", reencoded);
+    }
+
+    println!(
+        "{} {} games from {} to {} bytes, {} already in {:?}, skipped {}",
+        if args.dry_run {
+            "Would reencode"
+        } else {
+            "Reencoded"
+        },
+        reencoded,
+        old_bytes,
+        new_bytes,
+        unchanged,
+        args.to,
+        skipped
+    );
+    Ok(())
+}
@@ -0,0 +1,81 @@
+// stats.rs
+use crate::enums::*;
+use sqlx::PgPool;
+use std::error::Error;
+
+/// Prints counts of stored games and positions.
+pub async fn run(pool: &PgPool) -> Result<(), Box<dyn Error>> {
+    let games = sqlx::query!(
+        r#"
+        SELECT
+            COUNT(*) AS "games!",
+            COUNT(fingerprint) AS "fingerprinted!",
+            COUNT(plies) AS "with_plies!",
+            AVG(plies)::FLOAT8 AS average_plies,
+            SUM(plies)::BIGINT AS total_plies,
+            SUM(octet_length(pgn_moves)) FILTER (WHERE plies IS NOT NULL)::BIGINT AS move_bytes,
+            MIN(date) AS first_date,
+            MAX(date) AS last_date
+        FROM games
+        "#
+    )
+    .fetch_one(pool)
+    .await?;
+    let positions = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "positions!" FROM positions"#)
+        .fetch_one(pool)
+        .await?;
+
+    println!("Games: {}", games.games);
+    println!("Positions: {}", positions);
+    println!("Games with a fingerprint: {}", games.fingerprinted);
+    println!("Games with a ply count: {}", games.with_plies);
+    if let Some(average) = games.average_plies {
+        println!("Average plies: {:.1}", average);
+    }
+    // Only games with a ply count say how many moves their bytes hold
+    if let (Some(bytes), Some(plies)) = (games.move_bytes, games.total_plies) {
+        if plies > 0 {
+            println!(
+                "Average bits per move: {:.2}",
+                bytes as f64 * 8.0 / plies as f64
+            );
+        }
+    }
+    if let (Some(first), Some(last)) = (games.first_date, games.last_date) {
+        println!("Dates: {} to {}", first, last);
+    }
+
+    println!("By result:");
+    for row in sqlx::query!(
+        r#"
+        SELECT result AS "result!: ResultType", COUNT(*) AS "games!"
+        FROM games
+        GROUP BY result
+        ORDER BY 2 DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await?
+    {
+        println!("  {}: {}", row.result, row.games);
+    }
+
+    println!("By time control:");
+    for row in sqlx::query!(
+        r#"
+        SELECT time_control AS "time_control: ChessSpeed", COUNT(*) AS "games!"
+        FROM games
+        GROUP BY time_control
+        ORDER BY 2 DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await?
+    {
+        let name = row
+            .time_control
+            .map_or("unknown".to_string(), |tc| tc.to_string());
+        println!("  {}: {}", name, row.games);
+    }
+    Ok(())
+}
@@ -0,0 +1,135 @@
+// stored.rs
+use crate::cli::Filters;
+use crate::enums::*;
+use crate::Game;
+use chess_compression::{decompress_pgn_with_version, CodecVersion};
+use sqlx::types::chrono::NaiveDate;
+use sqlx::PgPool;
+use std::error::Error;
+
+/// The `codec_version` column value of a codec version.
+///
+/// Only frozen versions are stored, so an unfrozen one, which `parse_version`
+/// refuses, panics.
+pub fn codec_version_number(version: CodecVersion) -> i16 {
+    match version {
+        CodecVersion::V1 => 1,
+        CodecVersion::V2 => 2,
+        CodecVersion::V3 => 3,
+        CodecVersion::V4 => unreachable!("unfrozen codec versions are not stored"),
+    }
+}
+
+fn codec_version_from_number(number: i16) -> Option<CodecVersion> {
+    match number {
+        1 => Some(CodecVersion::V1),
+        2 => Some(CodecVersion::V2),
+        3 => Some(CodecVersion::V3),
+        _ => None,
+    }
+}
+
+/// A row of the games table.
+#[derive(Debug, Clone)]
+pub struct StoredGame {
+    pub id: i32,
+    pub eco: Option<String>,
+    pub white_player: String,
+    pub black_player: String,
+    pub date: Option<NaiveDate>,
+    pub result: ResultType,
+    pub white_elo: i32,
+    pub black_elo: i32,
+    pub time_control: Option<ChessSpeed>,
+    pub pgn_moves: Vec<u8>,
+    /// `None` for games imported before the ply count was stored.
+    pub plies: Option<i16>,
+    /// Codec version of `pgn_moves`, see `codec_version_number`.
+    pub codec_version: i16,
+    pub fingerprint: Option<Vec<u8>>,
+}
+
+impl StoredGame {
+    /// Returns the codec version of the moves, or an error for a version
+    /// this build does not know.
+    pub fn version(&self) -> Result<CodecVersion, Box<dyn Error>> {
+        codec_version_from_number(self.codec_version)
+            .ok_or_else(|| format!("unknown codec version {}", self.codec_version).into())
+    }
+
+    /// Decodes the SAN moves with their codec version, or returns `None` if
+    /// the ply count is unknown.
+    pub fn moves(&self) -> Option<Result<Vec<String>, Box<dyn Error>>> {
+        let plies = usize::try_from(self.plies?).ok()?;
+        Some(self.version().and_then(|version| {
+            Ok(decompress_pgn_with_version(
+                &self.pgn_moves,
+                plies,
+                version,
+            )?)
+        }))
+    }
+
+    /// The game as the importer saw it, with its decoded moves.
+    pub fn to_game(&self, moves: Vec<String>) -> Game {
+        Game {
+            eco: self.eco.clone().unwrap_or_default(),
+            white_player: self.white_player.clone(),
+            black_player: self.black_player.clone(),
+            date: self.date,
+            result: self.result,
+            pgn_moves: moves,
+            white_elo: self.white_elo,
+            black_elo: self.black_elo,
+            time_control: self.time_control,
+        }
+    }
+}
+
+/// Fetches up to `limit` games with an id above `after_id`, in id order.
+///
+/// Paging by id rather than by offset keeps every page an index range scan.
+pub async fn fetch_games(
+    pool: &PgPool,
+    filters: &Filters,
+    after_id: i32,
+    limit: i64,
+) -> Result<Vec<StoredGame>, sqlx::Error> {
+    sqlx::query_as!(
+        StoredGame,
+        r#"
+        SELECT
+            id,
+            eco,
+            white_player,
+            black_player,
+            date,
+            result AS "result: ResultType",
+            white_elo,
+            black_elo,
+            time_control AS "time_control: ChessSpeed",
+            pgn_moves,
+            plies,
+            codec_version,
+            fingerprint
+        FROM games
+        WHERE id > $1
+            AND ($2::INTEGER IS NULL OR LEAST(white_elo, black_elo) >= $2)
+            AND (cardinality($3::VARCHAR[]) = 0 OR time_control::VARCHAR = ANY($3))
+            AND ($4::DATE IS NULL OR date >= $4)
+            AND ($5::DATE IS NULL OR date <= $5)
+            AND ($6::VARCHAR IS NULL OR eco LIKE $6 || '%')
+        ORDER BY id
+        LIMIT $7
+        "#,
+        after_id,
+        filters.min_elo,
+        &filters.speed_names(),
+        filters.since,
+        filters.until,
+        filters.eco_prefix,
+        limit,
+    )
+    .fetch_all(pool)
+    .await
+}
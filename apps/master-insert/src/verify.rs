@@ -0,0 +1,103 @@
+// verify.rs
+use crate::cli::{Filters, VerifyArgs};
+use crate::stored::{fetch_games, StoredGame};
+use chess_compression::{transcode_moves, TranscodeOptions};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// Checks every stored game, printing one line per problem found.
+///
+/// A game passes when its moves decode, its fingerprint matches the one
+/// computed from its moves and metadata, and each of its stored positions
+/// matches the position reached at that ply. Fails if any game does not pass.
+pub async fn run(args: &VerifyArgs, pool: &PgPool) -> Result<(), Box<dyn Error>> {
+    let (mut after_id, mut checked, mut failed, mut unchecked) = (0, 0, 0, 0);
+    loop {
+        let games =
+            fetch_games(pool, &Filters::default(), after_id, args.batch_size as i64).await?;
+        let Some(last) = games.last() else {
+            break;
+        };
+        after_id = last.id;
+
+        let ids: Vec<i32> = games.iter().map(|game| game.id).collect();
+        let mut positions: HashMap<i32, Vec<(i16, Vec<u8>)>> = HashMap::new();
+        for row in sqlx::query!(
+            r#"
+            SELECT game_id, move_number, position
+            FROM positions
+            WHERE game_id = ANY($1)
+            "#,
+            &ids,
+        )
+        .fetch_all(pool)
+        .await?
+        {
+            positions
+                .entry(row.game_id)
+                .or_default()
+                .push((row.move_number, row.position));
+        }
+
+        for game in &games {
+            let stored_positions = positions.remove(&game.id).unwrap_or_default();
+            match check_game(game, &stored_positions) {
+                Some(Ok(())) => checked += 1,
+                Some(Err(problem)) => {
+                    println!("game {}: {}", game.id, problem);
+                    failed += 1;
+                }
+                None => unchecked += 1,
+            }
+        }
+        println!("Verified {} games", checked + failed + unchecked);
+    }
+
+    println!(
+        "{} games passed, {} failed, {} without a ply count were not checked",
+        checked, failed, unchecked
+    );
+    if failed > 0 {
+        return Err(format!("{} games failed verification", failed).into());
+    }
+    Ok(())
+}
+
+/// Returns `None` if the game has no ply count, so its moves cannot be decoded.
+fn check_game(game: &StoredGame, positions: &[(i16, Vec<u8>)]) -> Option<Result<(), String>> {
+    let moves = match game.moves()? {
+        Ok(moves) => moves,
+        Err(e) => return Some(Err(format!("moves do not decode: {}", e))),
+    };
+    Some(check_decoded(game, moves, positions))
+}
+
+fn check_decoded(
+    game: &StoredGame,
+    moves: Vec<String>,
+    positions: &[(i16, Vec<u8>)],
+) -> Result<(), String> {
+    let game_row = game.to_game(moves);
+    let fingerprint = game_row
+        .fingerprint()
+        .map_err(|e| format!("fingerprint failed: {}", e))?;
+    match &game.fingerprint {
+        Some(stored) if stored[..] == fingerprint.to_bytes() => {}
+        Some(_) => return Err("fingerprint does not match".to_string()),
+        None => return Err("fingerprint is missing, see backfill".to_string()),
+    }
+
+    let transcoded = transcode_moves(&game_row.pgn_moves, &TranscodeOptions::new())
+        .map_err(|e| format!("moves do not replay: {}", e))?;
+    for (ply, position) in positions {
+        let expected = usize::try_from(*ply)
+            .ok()
+            .and_then(|ply| ply.checked_sub(1))
+            .and_then(|index| transcoded.positions.get(index));
+        if expected.is_none_or(|expected| expected.position[..] != position[..]) {
+            return Err(format!("position at ply {} does not match the moves", ply));
+        }
+    }
+    Ok(())
+}
@@ -5,7 +5,18 @@ import wbgInit, {
   wasm_decompress_position,
   wasm_compress_pgn,
   wasm_decompress_pgn,
+  wasm_decompress_pgn_batch,
+  wasm_decompress_positions,
+  wasm_explore,
+  type ExplorerCounts as WasmExplorerCounts,
 } from "@openchess/chess-compression-wasm/pkg/web/chess_compression.js";
+import {
+  packGames,
+  unpackStrings,
+  type CompressedGame,
+} from "@/lib/compressed_games";
+
+export type { CompressedGame };
 
 let wasm_mod: InitOutput | null = null;
 let initPromise: Promise<void> | null = null;
@@ -75,5 +86,114 @@ export async function decompressPgn(
   }
 }
 
+export async function decompressPgnBatch(
+  games: CompressedGame[],
+): Promise<string[] | null> {
+  if (games.length === 0) return [];
+  try {
+    await ensureInitialized();
+    const { data, offsets, plies, codecVersions } = packGames(games);
+    return unpackStrings(
+      wasm_decompress_pgn_batch(data, offsets, plies, codecVersions),
+    );
+  } catch (error) {
+    console.error("PGN batch decompression failed:", error);
+    return null;
+  }
+}
+
+// `compressed` holds 32-byte positions back to back
+export async function decompressPositions(
+  compressed: Uint8Array,
+): Promise<string[] | null> {
+  if (compressed.length === 0) return [];
+  try {
+    await ensureInitialized();
+    return unpackStrings(wasm_decompress_positions(compressed));
+  } catch (error) {
+    console.error("Position batch decompression failed:", error);
+    return null;
+  }
+}
+
+// Games compressed with a result token carry their result; games from the
+// games table take theirs from its result column
+export interface ExplorerGame extends CompressedGame {
+  whiteElo?: number;
+  blackElo?: number;
+  result?: "white" | "black" | "draw";
+}
+
+// Result codes of wasm_explore, 0 when unknown
+const RESULT_CODES = { white: 1, black: 2, draw: 3 } as const;
+
+export interface ExplorerCounts {
+  games: number;
+  white: number;
+  draws: number;
+  black: number;
+  averageElo: number | null;
+}
+
+export interface ExplorerMove extends ExplorerCounts {
+  uci: string;
+  san: string;
+}
+
+export interface ExplorerResult extends ExplorerCounts {
+  moves: ExplorerMove[];
+}
+
+// Copies the counts out of wasm memory and frees them
+function readCounts(counts: WasmExplorerCounts): ExplorerCounts {
+  const copy = {
+    games: counts.games,
+    white: counts.white,
+    draws: counts.draws,
+    black: counts.black,
+    averageElo: counts.average_elo ?? null,
+  };
+  counts.free();
+  return copy;
+}
+
+// Next moves from `fen` across the given games, computed offline
+export async function explore(
+  fen: string,
+  games: ExplorerGame[],
+): Promise<ExplorerResult | null> {
+  try {
+    await ensureInitialized();
+    const { data, offsets, plies, codecVersions } = packGames(games);
+    const whiteElos = Int32Array.from(games, (game) => game.whiteElo ?? 0);
+    const blackElos = Int32Array.from(games, (game) => game.blackElo ?? 0);
+    const results = Uint8Array.from(games, (game) =>
+      game.result ? RESULT_CODES[game.result] : 0,
+    );
+    const result = wasm_explore(
+      fen,
+      data,
+      offsets,
+      plies,
+      whiteElos,
+      blackElos,
+      results,
+      codecVersions,
+    );
+    const moves = result.moves.map((move) => {
+      const explored = { uci: move.uci, san: move.san };
+      const counts = readCounts(move.counts);
+      move.free();
+      return { ...explored, ...counts };
+    });
+    const counts = readCounts(result.counts);
+    result.free();
+    return { ...counts, moves };
+  } catch (error) {
+    console.error("Explorer computation failed:", error);
+    return null;
+  }
+}
+
 // Optional: export initWasm for preloading if desired
 export const initWasm = ensureInitialized;
@@ -0,0 +1,52 @@
+// compressed_games.ts
+// Shared by the client and server wrappers of the wasm bindings
+
+export interface CompressedGame {
+  compressed: Uint8Array;
+  plies: number;
+  // The games table's codec_version column, 1 when missing
+  codecVersion?: number;
+}
+
+// Packs games back to back with the offsets, ply counts and codec versions
+// the batch bindings expect
+export function packGames(games: CompressedGame[]): {
+  data: Uint8Array;
+  offsets: Uint32Array;
+  plies: Uint32Array;
+  codecVersions: Uint8Array;
+} {
+  const offsets = new Uint32Array(games.length + 1);
+  games.forEach((game, i) => {
+    offsets[i + 1] = offsets[i] + game.compressed.length;
+  });
+  const data = new Uint8Array(offsets[games.length]);
+  games.forEach((game, i) => data.set(game.compressed, offsets[i]));
+  const plies = Uint32Array.from(games, (game) => game.plies);
+  const codecVersions = Uint8Array.from(
+    games,
+    (game) => game.codecVersion ?? 1,
+  );
+  return { data, offsets, plies, codecVersions };
+}
+
+// A StringBatch returned by the batch bindings, from either build
+interface StringBatch {
+  readonly data: Uint8Array;
+  readonly offsets: Uint32Array;
+  free(): void;
+}
+
+const utf8 = new TextDecoder();
+
+// Copies the strings out of a batch and frees it
+export function unpackStrings(batch: StringBatch): string[] {
+  const data = batch.data;
+  const offsets = batch.offsets;
+  batch.free();
+  const strings: string[] = [];
+  for (let i = 0; i + 1 < offsets.length; i++) {
+    strings.push(utf8.decode(data.subarray(offsets[i], offsets[i + 1])));
+  }
+  return strings;
+}
@@ -1,6 +1,40 @@
 // server_chess_compression.ts
 
 import * as wasm_mod from "@openchess/chess-compression-wasm/pkg/node";
+import {
+  packGames,
+  unpackStrings,
+  type CompressedGame,
+} from "@/lib/compressed_games";
+
+export type { CompressedGame };
+
+// Fields of the ChessCompressionError thrown by the wasm bindings
+export interface ChessCompressionError extends Error {
+  code: string;
+  ply: number | null;
+  san: string | null;
+  fen: string | null;
+  index: number | null;
+}
+
+function isChessCompressionError(
+  error: unknown,
+): error is ChessCompressionError {
+  return (
+    error instanceof Error &&
+    typeof (error as ChessCompressionError).code === "string"
+  );
+}
+
+export function describeError(error: unknown): string {
+  if (!isChessCompressionError(error)) {
+    return String(error);
+  }
+  const where = error.ply === null ? "" : ` at ply ${error.ply}`;
+  const move = error.san === null ? "" : ` (${error.san})`;
+  return `${error.code}${where}${move}: ${error.message}`;
+}
 
 export function compressPosition(fen: string): Uint8Array | null {
   try {
@@ -8,7 +42,7 @@ export function compressPosition(fen: string): Uint8Array | null {
     const compressed = wasm_mod.wasm_compress_position(fen);
     return compressed;
   } catch (error) {
-    console.error("Compression failed:", error);
+    console.error("Compression failed:", describeError(error));
     return null;
   }
 }
@@ -18,7 +52,7 @@ export function decompressPosition(compressed: Uint8Array): string | null {
     const decompressed = wasm_mod.wasm_decompress_position(compressed);
     return decompressed;
   } catch (error) {
-    console.error("Decompression failed:", error);
+    console.error("Decompression failed:", describeError(error));
     return null;
   }
 }
@@ -28,7 +62,7 @@ export function compressPgn(moves: string): Uint8Array | null {
     const compressed = wasm_mod.wasm_compress_pgn(moves);
     return compressed;
   } catch (error) {
-    console.error("PGN Compression failed:", error);
+    console.error("PGN Compression failed:", describeError(error));
     return null;
   }
 }
@@ -41,7 +75,60 @@ export function decompressPgn(
     const decompressed = wasm_mod.wasm_decompress_pgn(compressed, plies);
     return decompressed;
   } catch (error) {
-    console.error("PGN Decompression failed:", error);
+    console.error("PGN Decompression failed:", describeError(error));
+    return null;
+  }
+}
+
+export function decompressPgnBatch(games: CompressedGame[]): string[] | null {
+  if (games.length === 0) return [];
+  try {
+    const { data, offsets, plies, codecVersions } = packGames(games);
+    return unpackStrings(
+      wasm_mod.wasm_decompress_pgn_batch(data, offsets, plies, codecVersions),
+    );
+  } catch (error) {
+    console.error("PGN batch decompression failed:", describeError(error));
+    return null;
+  }
+}
+
+// `compressed` holds 32-byte positions back to back
+export function decompressPositions(compressed: Uint8Array): string[] | null {
+  if (compressed.length === 0) return [];
+  try {
+    return unpackStrings(wasm_mod.wasm_decompress_positions(compressed));
+  } catch (error) {
+    console.error("Position batch decompression failed:", describeError(error));
+    return null;
+  }
+}
+
+export interface ParsedGame {
+  headers: Record<string, string>;
+  moves: string[];
+  compressed: Uint8Array;
+  // Compressed position after each move, 32 bytes each
+  positions: Uint8Array;
+  error: ChessCompressionError | null;
+}
+
+// Parses uploaded PGN text the same way the master importer does
+export function parsePgn(pgn: string): ParsedGame[] | null {
+  try {
+    return wasm_mod.wasm_parse_pgn(pgn).map((game) => {
+      const parsed = {
+        headers: game.headers as Record<string, string>,
+        moves: game.moves,
+        compressed: game.compressed,
+        positions: game.positions,
+        error: game.error as ChessCompressionError | null,
+      };
+      game.free();
+      return parsed;
+    });
+  } catch (error) {
+    console.error("PGN parsing failed:", describeError(error));
     return null;
   }
 }
@@ -0,0 +1,6 @@
+//! WebAssembly build of `chess-compression`.
+//!
+//! The bindings are defined in `chess_compression::wasm`; this crate only
+//! links them into the module built by wasm-pack.
+
+pub use chess_compression::wasm::*;
@@ -0,0 +1,62 @@
+use chess_compression::{compress_pgn, decompress_pgn};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+// A handful of real games of different lengths and character
+const GAMES: &[&str] = &[
+    "e4 c5 Nf3 d6 Bb5+ Bd7 Bxd7+ Nxd7 O-O Ngf6 Re1 e6",
+    "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 d6 c3 O-O h3 Nb8 d4 Nbd7 c4 c6 cxb5 axb5 \
+     Nc3 Bb7 Bg5 b4 Nb1 h6 Bh4 c5 dxe5 Nxe4 Bxe7 Qxe7 exd6 Qf6 Nbd2 Nxd6 Nc4 Nxc4 Bxc4 Nb6 \
+     Ne5 Rae8 Bxf7+ Rxf7 Nxf7 Rxe1+ Qxe1 Kxf7 Qe3 Qg5 Qxg5 hxg5 b3 Ke6 a3 Kd6 axb4 cxb4 Ra5 \
+     Nd5 f3 Bc8 Kf2 Bf5 Ra7 g6 Ra6+ Kc5 Ke1 Nf4 g3 Nxh3 Kd2 Kb5 Rd6 Kc5 Ra6 Nf2 g4 Bd3 Re6",
+    "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3 O-O Be2 e5 O-O Nc6 d5 Ne7 Ne1 Nd7 Be3 f5 f3 f4 Bf2 g5 \
+     Nd3 Nf6 c5 Ng6 Rc1 Rf7 Kh1 h5 cxd6 cxd6 Nb5 a6 Na3 b5 Rc6 g4 Qc2 Qf8 Rc1 Bd7 Rc7 Bh6 \
+     Qd2 g3 Bg1 gxh2 Bf2 Ng4 fxg4 hxg4 Bxg4 Nh4 Bxh4 f3 Bxf3 Rxf3",
+    "e4 c5 d3 g6 f4 Bg7 Nf3 e6 e5 d5 exd6 Qxd6 Nc3 Ne7 Ne4 Qd8 Nxc5 Qa5+ c3 Qxc5 d4 Qc7 \
+     Bb5+ Nbc6 O-O O-O Ne5 Nxe5 fxe5 Bxe5 dxe5 Qc5+ Kh1 Qxb5 Bh6 Re8 Qf3 Nf5 g4 Nxh6 Qf4 \
+     Qc6+ Kg1 Qc5+ Kh1 Qd5+ Kg1 Kg7 Qf6+ Kg8 Qf4 Qc5+ Rf2 Bd7 Qxh6 Bc6 Qf4 Rf8 h4 Qd5 Rh2 \
+     Rad8 h5 Qc5+ Rf2 Rd5 hxg6 fxg6 Qxf8+ Qxf8 Rxf8+ Kxf8 Re1 Kg7 Kf2 Rd2+ Re2 Rxe2+ Kxe2 \
+     Bd5 Ke3 Bxa2 Kf4 Bd5 Kg5 Bc6 b4 a5 bxa5 Bb5 Kf4 h6 Kg3 g5 Kh3 Ba6 Kg3 Kg6 Kh3 Kf7 Kg3 \
+     Kg6 Kf3 Bd3 Kg3 Be4 Kh3 Bc6 Kg3 h5 Kh3 hxg4+ Kxg4 Be4 Kg3 Bf5 Kf3 Bd3 Kg3 Kf5 Kf3 \
+     g4+ Kg3 Kxe5 Kxg4 Kd5 Kf4 Kc4 Ke5 Kxc3 Kxe6 Ba6 Kd6",
+];
+
+fn games() -> Vec<Vec<String>> {
+    GAMES
+        .iter()
+        .map(|game| game.split_whitespace().map(String::from).collect())
+        .collect()
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let games = games();
+    let mut group = c.benchmark_group("encode");
+    group.throughput(Throughput::Elements(games.len() as u64));
+    group.bench_function("compress_pgn", |b| {
+        b.iter(|| {
+            for moves in &games {
+                black_box(compress_pgn(black_box(moves)).unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let compressed: Vec<(Vec<u8>, usize)> = games()
+        .iter()
+        .map(|moves| (compress_pgn(moves).unwrap(), moves.len()))
+        .collect();
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Elements(compressed.len() as u64));
+    group.bench_function("decompress_pgn", |b| {
+        b.iter(|| {
+            for (bytes, plies) in &compressed {
+                black_box(decompress_pgn(black_box(bytes), *plies).unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_encode, bench_decode);
+criterion_main!(benches);
@@ -0,0 +1,193 @@
+// build.rs
+//! Builds the codebooks and the ECO opening book of the game formats into
+//! static tables, so they need no initialization at runtime, with or without
+//! `std`.
+//!
+//! The codebooks are built by the crate's own `huffman` and `frequencies`
+//! modules, included here, and written to `$OUT_DIR/code_tables.rs`, which
+//! `huffman_code` includes. The book is built by `opening_book` and written to
+//! `$OUT_DIR/eco_book.rs`, which `eco_book` includes.
+
+extern crate alloc;
+
+#[allow(dead_code)]
+#[path = "src/frequencies.rs"]
+mod frequencies;
+#[allow(dead_code)]
+#[path = "src/huffman.rs"]
+mod huffman;
+#[allow(dead_code)]
+#[path = "src/opening_book.rs"]
+mod opening_book;
+#[path = "src/phase_counts.rs"]
+mod phase_counts;
+
+use frequencies::{fit_phase_codes, FREQUENCIES};
+use huffman::{Codebook, Entry};
+use opening_book::{OpeningBook, ECO_LINES};
+use phase_counts::PHASE_COUNTS;
+use shakmaty::{Move, Role, Square};
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+fn write_codebook(out: &mut String, codebook: &Codebook) {
+    let (codes, table, root_bits) = codebook.parts();
+    out.push_str("Codebook::from_static(\n    &[\n");
+    for code in codes {
+        writeln!(
+            out,
+            "        Code {{ bits: {}, len: {} }},",
+            code.bits, code.len
+        )
+        .unwrap();
+    }
+    out.push_str("    ],\n    &[\n");
+    for entry in table {
+        match *entry {
+            Entry::Invalid => out.push_str("        Entry::Invalid,\n"),
+            Entry::Symbol { symbol, len } => writeln!(
+                out,
+                "        Entry::Symbol {{ symbol: {}, len: {} }},",
+                symbol, len
+            )
+            .unwrap(),
+            Entry::Subtable { start, bits } => writeln!(
+                out,
+                "        Entry::Subtable {{ start: {}, bits: {} }},",
+                start, bits
+            )
+            .unwrap(),
+        }
+    }
+    writeln!(out, "    ],\n    {},\n)", root_bits).unwrap();
+}
+
+fn square(square: Square) -> String {
+    format!("Square::{}", square.to_string().to_uppercase())
+}
+
+fn role(role: Option<Role>) -> String {
+    match role {
+        Some(role) => format!("Some(Role::{:?})", role),
+        None => "None".to_string(),
+    }
+}
+
+fn write_move(out: &mut String, mv: &Move) {
+    match *mv {
+        Move::Normal {
+            role: moved,
+            from,
+            capture,
+            to,
+            promotion,
+        } => write!(
+            out,
+            "Move::Normal {{ role: Role::{:?}, from: {}, capture: {}, to: {}, promotion: {} }}",
+            moved,
+            square(from),
+            role(capture),
+            square(to),
+            role(promotion)
+        ),
+        Move::EnPassant { from, to } => write!(
+            out,
+            "Move::EnPassant {{ from: {}, to: {} }}",
+            square(from),
+            square(to)
+        ),
+        Move::Castle { king, rook } => write!(
+            out,
+            "Move::Castle {{ king: {}, rook: {} }}",
+            square(king),
+            square(rook)
+        ),
+        Move::Put { role: put, to } => write!(
+            out,
+            "Move::Put {{ role: Role::{:?}, to: {} }}",
+            put,
+            square(to)
+        ),
+    }
+    .unwrap();
+}
+
+fn write_book(out: &mut String, book: &OpeningBook) {
+    out.push_str("OpeningBook::from_static(&[\n");
+    for node in book.nodes() {
+        write!(
+            out,
+            "    Node {{\n        parent: {},\n        mv: ",
+            node.parent
+        )
+        .unwrap();
+        match &node.mv {
+            Some(mv) => {
+                out.push_str("Some(");
+                write_move(out, mv);
+                out.push(')');
+            }
+            None => out.push_str("None"),
+        }
+        writeln!(
+            out,
+            ",\n        depth: {},\n        children: Cow::Borrowed(&[",
+            node.depth
+        )
+        .unwrap();
+        for (mv, child) in node.children.iter() {
+            out.push_str("            (");
+            write_move(out, mv);
+            writeln!(out, ", {}),", child).unwrap();
+        }
+        out.push_str("        ]),\n    },\n");
+    }
+    out.push_str("])");
+}
+
+fn main() {
+    for path in [
+        "src/frequencies.rs",
+        "src/huffman.rs",
+        "src/opening_book.rs",
+        "src/phase_counts.rs",
+    ] {
+        println!("cargo:rerun-if-changed={}", path);
+    }
+
+    let weights = FREQUENCIES
+        .iter()
+        .map(|&(symbol, freq)| (symbol, freq.into()));
+    let huffman_code =
+        Codebook::from_weights_tree_order(weights).expect("frequency table codes fit in 32 bits");
+    let phase_codes = fit_phase_codes(&PHASE_COUNTS);
+
+    let mut out = String::from("// Generated by build.rs; do not edit.\n\n");
+    out.push_str("static HUFFMAN_CODE: Codebook = ");
+    write_codebook(&mut out, &huffman_code);
+    out.push_str(";\n\n");
+    writeln!(
+        out,
+        "static PHASE_CODES: [Codebook; {}] = [",
+        phase_codes.len()
+    )
+    .unwrap();
+    for codebook in &phase_codes {
+        write_codebook(&mut out, codebook);
+        out.push_str(",\n");
+    }
+    out.push_str("];\n");
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("code_tables.rs"), out).unwrap();
+
+    let eco_book = OpeningBook::from_lines(ECO_LINES.iter().copied())
+        .expect("built-in ECO lines must be legal");
+    let mut out = String::from("// Generated by build.rs; do not edit.\n\n");
+    out.push_str("static ECO_BOOK: OpeningBook = ");
+    write_book(&mut out, &eco_book);
+    out.push_str(";\n");
+    fs::write(Path::new(&out_dir).join("eco_book.rs"), out).unwrap();
+}
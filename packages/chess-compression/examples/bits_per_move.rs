@@ -0,0 +1,252 @@
+//! Reports the average number of bits per move each codec version needs on a
+//! PGN corpus, and its size relative to V1.
+//!
+//! ```sh
+//! cargo run --release --example bits_per_move -- games.pgn [more.pgn ...]
+//! cargo run --release --example bits_per_move -- --book-from train.pgn games.pgn
+//! cargo run --release --example bits_per_move -- --fit-from train.pgn games.pgn
+//! ```
+//!
+//! V4 is also reported with the truncated V1 codebooks it would have without
+//! the counts of `fit_phase_codes`, which shows the gain from those counts.
+//! That gain is only meaningful on games the counts were not fitted on.
+//!
+//! With `--fit-from`, V4 codebooks are fitted to the games of that file, as
+//! `fit_phase_codes` would, and V4 with them is reported as well. Measuring
+//! other files than the training one gives the gain on held-out games.
+//!
+//! With `--book-from`, an opening book is built from the games of that file
+//! with `OpeningBook::from_games`, and V3 with that book in place of the ECO
+//! book is reported as well. Building it from a different file than the one
+//! measured avoids crediting the book with lines it was built from.
+use chess_compression::{
+    fit_phase_codes, Codebook, CodecVersion, Encoder, OpeningBook, MOVE_COUNT_BOUNDS,
+};
+use pgn_reader::{BufferedReader, SanPlus, Skip, Visitor};
+use std::env;
+use std::error::Error;
+use std::fs::File;
+
+const VERSIONS: [CodecVersion; 4] = [
+    CodecVersion::V1,
+    CodecVersion::V2,
+    CodecVersion::V3,
+    CodecVersion::V4,
+];
+
+/// Lines of a corpus book are cut after this many moves...
+const BOOK_MAX_PLIES: usize = 20;
+/// ...and kept only if this many games of the training file share them.
+const BOOK_MIN_GAMES: usize = 5;
+
+/// A codec to measure: a version, with its own book or another one, and its
+/// own codebooks or V4-style ones.
+struct Codec {
+    label: String,
+    version: CodecVersion,
+    book: Option<OpeningBook>,
+    // One per bucket of `MOVE_COUNT_BOUNDS`, replacing the version's codebooks
+    codebooks: Option<Vec<Codebook>>,
+}
+
+impl Codec {
+    fn encoder(&self) -> Encoder<'_> {
+        match &self.book {
+            Some(book) => Encoder::with_book(self.version, book),
+            None => Encoder::with_version(self.version),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    games: u64,
+    plies: u64,
+    bits: Vec<u64>,
+    failed: u64,
+}
+
+struct MoveCollector {
+    moves: Vec<String>,
+}
+
+impl Visitor for MoveCollector {
+    type Result = ();
+
+    fn begin_game(&mut self) {
+        self.moves.clear();
+    }
+
+    fn san(&mut self, san_plus: SanPlus) {
+        self.moves.push(san_plus.to_string());
+    }
+
+    fn begin_variation(&mut self) -> Skip {
+        Skip(true)
+    }
+
+    fn end_game(&mut self) -> Self::Result {}
+}
+
+fn code_len(codebook: Option<&Codebook>, index: u32) -> i64 {
+    codebook
+        .and_then(|codebook| codebook.code_len(index))
+        .map_or(0, i64::from)
+}
+
+fn encoded_bits(moves: &[String], codec: &Codec) -> Option<u64> {
+    let mut encoder = codec.encoder();
+    // Bits of the other codebooks minus those of the version's
+    let mut replaced = 0i64;
+    for mv in moves {
+        let rank = encoder.encode_move_ranked(mv).ok()?;
+        if let (Some(codebooks), Some((index, move_count))) = (&codec.codebooks, rank) {
+            if move_count > 1 {
+                let bucket = MOVE_COUNT_BOUNDS.partition_point(|&bound| bound < move_count);
+                let codebook = codebooks.get(bucket).or(codebooks.last());
+                replaced +=
+                    code_len(codebook, index) - code_len(codec.version.codebook(move_count), index);
+            }
+        }
+    }
+    Some((encoder.finalize().len() as i64 + replaced) as u64)
+}
+
+/// Codebooks fitted to the move indices played in `games`, as by `fit_phase_codes`.
+fn fitted_codebooks(games: &[Vec<String>]) -> Vec<Codebook> {
+    let mut counts: Vec<Vec<u32>> = MOVE_COUNT_BOUNDS
+        .iter()
+        .map(|&bound| vec![0; bound])
+        .collect();
+    for moves in games {
+        let mut encoder = Encoder::with_version(CodecVersion::V4);
+        for mv in moves {
+            let Ok(rank) = encoder.encode_move_ranked(mv) else {
+                break;
+            };
+            if let Some((index, move_count)) = rank.filter(|&(_, move_count)| move_count > 1) {
+                let bucket = MOVE_COUNT_BOUNDS
+                    .partition_point(|&bound| bound < move_count)
+                    .min(MOVE_COUNT_BOUNDS.len() - 1);
+                counts[bucket][index as usize] += 1;
+            }
+        }
+    }
+    fit_phase_codes(&counts)
+}
+
+fn read_games(path: &str) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+    let mut reader = BufferedReader::new(File::open(path)?);
+    let mut collector = MoveCollector { moves: Vec::new() };
+    let mut games = Vec::new();
+    while reader.read_game(&mut collector)?.is_some() {
+        games.push(collector.moves.clone());
+    }
+    Ok(games)
+}
+
+/// Removes `name` and the value after it from `args`, and returns the value.
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    match args.iter().position(|arg| arg == name) {
+        Some(index) if index + 1 < args.len() => {
+            let value = args.remove(index + 1);
+            args.remove(index);
+            Some(value)
+        }
+        _ => None,
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let book_path = take_option(&mut args, "--book-from");
+    let fit_path = take_option(&mut args, "--fit-from");
+    if args.is_empty() {
+        eprintln!(
+            "Usage: bits_per_move [--book-from <pgn_file>] [--fit-from <pgn_file>] <pgn_file>..."
+        );
+        std::process::exit(1);
+    }
+
+    let mut codecs: Vec<Codec> = VERSIONS
+        .iter()
+        .map(|&version| Codec {
+            label: format!("{:?}", version),
+            version,
+            book: None,
+            codebooks: None,
+        })
+        .collect();
+    codecs.push(Codec {
+        label: "V4 untrained".to_string(),
+        version: CodecVersion::V4,
+        book: None,
+        codebooks: Some(fit_phase_codes::<&[u32]>(&[])),
+    });
+    if let Some(path) = &book_path {
+        let book = OpeningBook::from_games(&read_games(path)?, BOOK_MAX_PLIES, BOOK_MIN_GAMES)?;
+        println!(
+            "Book from {}: {} nodes, {} bits per node ID",
+            path,
+            book.len(),
+            book.id_bits()
+        );
+        codecs.push(Codec {
+            label: "V3 + corpus book".to_string(),
+            version: CodecVersion::V3,
+            book: Some(book),
+            codebooks: None,
+        });
+    }
+
+    if let Some(path) = &fit_path {
+        codecs.push(Codec {
+            label: format!("V4 fitted on {}", path),
+            version: CodecVersion::V4,
+            book: None,
+            codebooks: Some(fitted_codebooks(&read_games(path)?)),
+        });
+    }
+
+    let mut stats = Stats {
+        bits: vec![0; codecs.len()],
+        ..Stats::default()
+    };
+    let mut collector = MoveCollector { moves: Vec::new() };
+
+    for path in &args {
+        let mut reader = BufferedReader::new(File::open(path)?);
+        while reader.read_game(&mut collector)?.is_some() {
+            let bits: Option<Vec<u64>> = codecs
+                .iter()
+                .map(|codec| encoded_bits(&collector.moves, codec))
+                .collect();
+            match bits {
+                Some(bits) => {
+                    stats.games += 1;
+                    stats.plies += collector.moves.len() as u64;
+                    for (total, bits) in stats.bits.iter_mut().zip(bits) {
+                        *total += bits;
+                    }
+                }
+                None => stats.failed += 1,
+            }
+        }
+    }
+
+    println!(
+        "{} games, {} plies ({} skipped)",
+        stats.games, stats.plies, stats.failed
+    );
+    for (codec, &bits) in codecs.iter().zip(&stats.bits) {
+        println!(
+            "{}: {:.3} bits/move, {:.1} bytes/game, {:.1}% of V1",
+            codec.label,
+            bits as f64 / stats.plies.max(1) as f64,
+            bits as f64 / 8.0 / stats.games.max(1) as f64,
+            100.0 * bits as f64 / stats.bits[0].max(1) as f64
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,177 @@
+//! Counts the move indices played in each V4 move count bucket of a PGN
+//! corpus, and writes them as the `PHASE_COUNTS` table the V4 codebooks are
+//! built from.
+//!
+//! ```sh
+//! sha256sum games.pgn
+//! cargo run --release --example fit_phase_codes -- \
+//!     --source https://example.org/games.pgn --sha256 <hash> games.pgn > src/phase_counts.rs
+//! ```
+//!
+//! The corpus must be downloadable, so `--source` and `--sha256` are required
+//! and written to the header of the table: anyone can fetch the same files,
+//! check them and regenerate the table. Several files are pinned by repeating
+//! both options, in the order of the files.
+//!
+//! Only moves coded with a codebook are counted: book moves and forced moves
+//! are not. The bits per coded move of the corpus with the fitted codebooks
+//! and with the truncated V1 ones are printed to stderr. Replacing the table
+//! changes the V4 format.
+use chess_compression::{fit_phase_codes, Codebook, CodecVersion, Encoder, MOVE_COUNT_BOUNDS};
+use pgn_reader::{BufferedReader, SanPlus, Skip, Visitor};
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+
+struct MoveCollector {
+    moves: Vec<String>,
+}
+
+impl Visitor for MoveCollector {
+    type Result = ();
+
+    fn begin_game(&mut self) {
+        self.moves.clear();
+    }
+
+    fn san(&mut self, san_plus: SanPlus) {
+        self.moves.push(san_plus.to_string());
+    }
+
+    fn begin_variation(&mut self) -> Skip {
+        Skip(true)
+    }
+
+    fn end_game(&mut self) -> Self::Result {}
+}
+
+/// Move indices and legal move counts of the coded moves of a game.
+fn ranked_moves(moves: &[String]) -> Option<Vec<(u32, usize)>> {
+    let mut encoder = Encoder::with_version(CodecVersion::V4);
+    let mut ranks = Vec::with_capacity(moves.len());
+    for mv in moves {
+        if let Some((index, move_count)) = encoder.encode_move_ranked(mv).ok()? {
+            if move_count > 1 {
+                ranks.push((index, move_count));
+            }
+        }
+    }
+    Some(ranks)
+}
+
+fn bucket(move_count: usize) -> usize {
+    MOVE_COUNT_BOUNDS
+        .partition_point(|&bound| bound < move_count)
+        .min(MOVE_COUNT_BOUNDS.len() - 1)
+}
+
+/// Total code length of the counted moves with the given codebooks.
+fn coded_bits(counts: &[Vec<u32>], codebooks: &[Codebook]) -> u64 {
+    counts
+        .iter()
+        .zip(codebooks)
+        .map(|(counts, codebook)| {
+            counts
+                .iter()
+                .zip(codebook.code_lengths())
+                .map(|(&count, len)| u64::from(count) * u64::from(len))
+                .sum::<u64>()
+        })
+        .sum()
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut paths = Vec::new();
+    let (mut sources, mut hashes) = (Vec::new(), Vec::new());
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--source" => sources.extend(args.next()),
+            "--sha256" => hashes.extend(args.next()),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() || sources.len() != paths.len() || hashes.len() != paths.len() {
+        eprintln!(
+            "Usage: fit_phase_codes (--source <url> --sha256 <hash>)... <pgn_file>... > src/phase_counts.rs"
+        );
+        std::process::exit(1);
+    }
+
+    let mut counts: Vec<Vec<u32>> = MOVE_COUNT_BOUNDS
+        .iter()
+        .map(|&bound| vec![0; bound])
+        .collect();
+    let (mut games, mut failed) = (0u64, 0u64);
+    let mut collector = MoveCollector { moves: Vec::new() };
+    for path in &paths {
+        let mut reader = BufferedReader::new(File::open(path)?);
+        while reader.read_game(&mut collector)?.is_some() {
+            let Some(ranks) = ranked_moves(&collector.moves) else {
+                failed += 1;
+                continue;
+            };
+            games += 1;
+            for (index, move_count) in ranks {
+                counts[bucket(move_count)][index as usize] += 1;
+            }
+        }
+    }
+
+    let coded: u64 = counts.iter().flatten().map(|&count| u64::from(count)).sum();
+    let fitted = coded_bits(&counts, &fit_phase_codes(&counts));
+    let untrained = coded_bits(&counts, &fit_phase_codes::<&[u32]>(&[]));
+    eprintln!(
+        "{} games, {} coded moves ({} skipped): {:.3} bits per coded move fitted, {:.3} untrained",
+        games,
+        coded,
+        failed,
+        fitted as f64 / coded.max(1) as f64,
+        untrained as f64 / coded.max(1) as f64
+    );
+
+    let names: Vec<&str> = paths
+        .iter()
+        .map(|path| {
+            let path = Path::new(path);
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or("?")
+        })
+        .collect();
+    println!("// src/phase_counts.rs");
+    println!("// Generated by `cargo run --release --example fit_phase_codes`; do not edit.");
+    println!(
+        "// Corpus: {}, {} games, {} coded moves.",
+        names.join(", "),
+        games,
+        coded
+    );
+    for (source, hash) in sources.iter().zip(&hashes) {
+        println!("// Source: {} (SHA-256 {})", source, hash);
+    }
+    println!();
+    println!("/// How often each move index was played in the positions of each bucket of");
+    println!("/// `MOVE_COUNT_BOUNDS`, counting only the moves coded with a V4 codebook.");
+    println!("#[rustfmt::skip]");
+    println!(
+        "pub(crate) static PHASE_COUNTS: [&[u32]; {}] = [",
+        MOVE_COUNT_BOUNDS.len()
+    );
+    for bucket in &counts {
+        // Trailing zeros are implied
+        let len = bucket
+            .iter()
+            .rposition(|&count| count > 0)
+            .map_or(0, |i| i + 1);
+        println!("    &[");
+        for line in bucket[..len].chunks(12) {
+            let values: Vec<String> = line.iter().map(u32::to_string).collect();
+            println!("        {},", values.join(", "));
+        }
+        println!("    ],");
+    }
+    println!("];");
+    Ok(())
+}
@@ -0,0 +1,473 @@
+// src/decoder.rs
+use crate::huffman::BitReader;
+use crate::opening_book::OpeningBook;
+use crate::outcome::GameOutcome;
+use crate::pgn_compress::{san_to_move, CodecVersion, Encoder, EncoderError, ErrorContext};
+use crate::ranking::MoveRanker;
+use alloc::{format, string::String, vec::Vec};
+use bit_vec::BitVec;
+use shakmaty::{
+    san::{San, SanPlus, Suffix},
+    Chess, Move, Position,
+};
+
+/// Decodes games compressed by an `Encoder`.
+///
+/// A decoder holds no per-game state, only references to the static code
+/// tables and to its opening book, so it is cheap to copy and can be shared
+/// across threads.
+#[derive(Debug, Clone, Copy)]
+pub struct Decoder<'a> {
+    version: CodecVersion,
+    book: Option<&'a OpeningBook>,
+}
+
+impl Default for Decoder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new() -> Self {
+        Self::with_version(CodecVersion::default())
+    }
+
+    /// Creates a decoder for games encoded with the given codec version.
+    pub fn with_version(version: CodecVersion) -> Self {
+        Decoder {
+            version,
+            book: version.opening_book(),
+        }
+    }
+
+    /// Creates a decoder for games encoded by `Encoder::with_book` with the
+    /// same version and book.
+    pub fn with_book(version: CodecVersion, book: &'a OpeningBook) -> Self {
+        Decoder {
+            version,
+            book: Some(book),
+        }
+    }
+
+    /// Returns the codec version used by this decoder.
+    pub fn version(&self) -> CodecVersion {
+        self.version
+    }
+
+    /// Decodes the compressed data into PGN moves.
+    ///
+    /// An outcome trailer is ignored; use `decode_with_outcome` to read it.
+    pub fn decode(&self, data: &BitVec, plies: usize) -> Result<Vec<String>, EncoderError> {
+        self.decode_with_outcome(data, plies)
+            .map(|(moves, _)| moves)
+    }
+
+    /// Decodes the compressed data into PGN moves and the optional outcome trailer.
+    ///
+    /// `plies` must be the exact number of encoded moves for the trailer to
+    /// be found.
+    pub fn decode_with_outcome(
+        &self,
+        data: &BitVec,
+        plies: usize,
+    ) -> Result<(Vec<String>, Option<GameOutcome>), EncoderError> {
+        // `plies` may come from untrusted storage, so it does not size allocations
+        let mut output = Vec::with_capacity(plies.min(data.len() + 32));
+        let mut moves = self.decode_iter(data, plies);
+
+        while let Some(mv) = moves.next_move() {
+            let mv = mv?;
+            let san = San::from_move(&moves.board, &mv);
+            moves.play(mv);
+            let suffix = Suffix::from_position(&moves.board);
+            output.push(format!("{}", SanPlus { san, suffix }));
+        }
+
+        Ok((output, moves.outcome()))
+    }
+
+    /// Returns an iterator over the moves of a compressed game.
+    ///
+    /// `plies` is the number of encoded moves. Decoding stops after them or
+    /// at the first error; to decode a prefix, stop iterating early.
+    pub fn decode_iter<'d>(&self, data: &'d BitVec, plies: usize) -> DecodeIter<'d>
+    where
+        'a: 'd,
+    {
+        DecodeIter {
+            version: self.version,
+            book: self.book,
+            bits: BitReader::new(data),
+            board: Chess::default(),
+            ranker: MoveRanker::new(self.version.ranking_model()),
+            last_move: None,
+            book_line: Vec::new(),
+            ply: 0,
+            plies,
+        }
+    }
+
+    /// Decodes the compressed data into the position after each move.
+    pub fn decode_to_positions(
+        &self,
+        data: &BitVec,
+        plies: usize,
+    ) -> Result<Vec<Chess>, EncoderError> {
+        let mut positions = Vec::with_capacity(plies.min(data.len() + 32));
+        let mut moves = self.decode_iter(data, plies);
+        while let Some(mv) = moves.next() {
+            mv?;
+            positions.push(moves.position().clone());
+        }
+        Ok(positions)
+    }
+
+    /// Returns the number of moves two compressed games have in common from
+    /// the start, decoding only up to the first move that differs.
+    ///
+    /// The games are the bytes of `Encoder::finalize`, as stored.
+    pub fn common_prefix_plies(
+        &self,
+        a: &[u8],
+        a_plies: usize,
+        b: &[u8],
+        b_plies: usize,
+    ) -> Result<usize, EncoderError> {
+        let (a, b) = (BitVec::from_bytes(a), BitVec::from_bytes(b));
+        let plies = a_plies.min(b_plies);
+        let a_moves = self.decode_iter(&a, a_plies);
+        let b_moves = self.decode_iter(&b, b_plies);
+        for (ply, (a_move, b_move)) in a_moves.zip(b_moves).take(plies).enumerate() {
+            if a_move? != b_move? {
+                return Ok(ply);
+            }
+        }
+        Ok(plies)
+    }
+
+    /// Returns whether a compressed game starts with the given SAN moves.
+    ///
+    /// The game is the bytes of `Encoder::finalize`, as stored. A line that
+    /// is not legal from the start position matches no game, so it returns
+    /// `Ok(false)`; errors come only from the game's data.
+    ///
+    /// Without an opening book, move codes depend only on the moves before
+    /// them, so the line is encoded and compared bit by bit with the game
+    /// without decoding it.
+    pub fn starts_with(
+        &self,
+        game: &[u8],
+        plies: usize,
+        line: &[String],
+    ) -> Result<bool, EncoderError> {
+        if line.len() > plies {
+            return Ok(false);
+        }
+        let game = BitVec::from_bytes(game);
+
+        if self.book.is_none() {
+            let mut encoder = Encoder::with_version(self.version);
+            for mv in line {
+                if encoder.encode_move(mv).is_err() {
+                    return Ok(false);
+                }
+            }
+            let prefix = encoder.finalize();
+            return Ok(prefix.len() <= game.len() && prefix.iter().zip(&game).all(|(a, b)| a == b));
+        }
+
+        // Book node IDs hide the shared moves, so compare decoded moves instead
+        let mut moves = self.decode_iter(&game, plies);
+        for san in line {
+            let Ok(expected) = san_to_move(moves.position(), moves.ply(), san) else {
+                return Ok(false);
+            };
+            if moves.next().transpose()? != Some(expected) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Iterator over the moves of a compressed game, created by `Decoder::decode_iter`.
+pub struct DecodeIter<'a> {
+    version: CodecVersion,
+    // Taken once the book node at the start of the data has been read
+    book: Option<&'a OpeningBook>,
+    bits: BitReader<'a>,
+    board: Chess,
+    ranker: MoveRanker,
+    last_move: Option<Move>,
+    // Remaining moves of the book line, in reverse order
+    book_line: Vec<Move>,
+    ply: usize,
+    plies: usize,
+}
+
+impl DecodeIter<'_> {
+    /// Returns the position after the moves decoded so far.
+    pub fn position(&self) -> &Chess {
+        &self.board
+    }
+
+    /// Returns the number of moves decoded so far.
+    pub fn ply(&self) -> usize {
+        self.ply
+    }
+
+    /// Reads the outcome trailer once every move has been decoded.
+    ///
+    /// Returns `None` if moves are left or the game has no trailer.
+    pub fn outcome(mut self) -> Option<GameOutcome> {
+        if self.ply < self.plies {
+            return None;
+        }
+        // A game without moves still starts with the book root
+        self.read_book().ok()?;
+        GameOutcome::read_trailer(self.bits)
+    }
+
+    // Reads the book node at the start of the data, the first time it is called
+    fn read_book(&mut self) -> Result<(), EncoderError> {
+        let Some(book) = self.book.take() else {
+            return Ok(());
+        };
+        match read_book_node(&mut self.bits, book).and_then(|node| book.line(node)) {
+            // The encoder writes the node reached within the game, so a
+            // longer line means the data or the ply count is wrong
+            Some(mut line) if line.len() <= self.plies => {
+                line.reverse();
+                self.book_line = line;
+                Ok(())
+            }
+            _ => {
+                // Stop after reporting the error
+                self.plies = 0;
+                Err(EncoderError::InvalidOpeningNode {
+                    context: ErrorContext::new(0, None, &self.board),
+                })
+            }
+        }
+    }
+
+    // Decodes the next move without playing it
+    fn next_move(&mut self) -> Option<Result<Move, EncoderError>> {
+        if self.ply >= self.plies {
+            return None;
+        }
+
+        if let Err(error) = self.read_book() {
+            return Some(Err(error));
+        }
+        if let Some(mv) = self.book_line.pop() {
+            return Some(Ok(mv));
+        }
+
+        self.ranker.fill(&self.board, self.last_move.as_ref());
+        let index = match self.version.codebook(self.ranker.move_count()) {
+            Some(codebook) => codebook.decode(&mut self.bits),
+            None => Some(0),
+        };
+        let mv = index
+            .and_then(|index| self.ranker.select(index as usize))
+            .cloned();
+        match mv {
+            Some(mv) => Some(Ok(mv)),
+            None => {
+                let context = ErrorContext::new(self.ply, None, &self.board);
+                self.plies = self.ply;
+                Some(Err(EncoderError::InvalidMoveIndex { context }))
+            }
+        }
+    }
+
+    fn play(&mut self, mv: Move) {
+        // Decoded moves come from the legal move list, so they can be played in place
+        self.board.play_unchecked(&mv);
+        self.last_move = Some(mv);
+        self.ply += 1;
+    }
+}
+
+impl Iterator for DecodeIter<'_> {
+    type Item = Result<Move, EncoderError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mv = self.next_move()?;
+        if let Ok(mv) = &mv {
+            self.play(mv.clone());
+        }
+        Some(mv)
+    }
+}
+
+fn read_book_node(bits: &mut impl Iterator<Item = bool>, book: &OpeningBook) -> Option<u32> {
+    let mut node = 0;
+    for _ in 0..book.id_bits() {
+        node = (node << 1) | u32::from(bits.next()?);
+    }
+    (node < book.len() as u32).then_some(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn_compress::Encoder;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_decoder_is_send_and_sync() {
+        assert_send_sync::<Decoder>();
+    }
+
+    #[test]
+    fn test_decode_iter_and_positions() -> Result<(), EncoderError> {
+        let pgn_moves = vec![
+            "e4", "c5", "Nf3", "d6", "d4", "cxd4", "Nxd4", "Nf6", "Nc3", "a6", "Be3", "e5", "Nb3",
+            "Be6", "f3", "h5", "Qd2", "Nbd7",
+        ];
+
+        for version in [
+            CodecVersion::V1,
+            CodecVersion::V2,
+            CodecVersion::V3,
+            CodecVersion::V4,
+        ] {
+            let mut encoder = Encoder::with_version(version);
+            let mut expected = Vec::new();
+            for mv in &pgn_moves {
+                encoder.encode_move(mv)?;
+                expected.push(encoder.position().clone());
+            }
+            let compressed = encoder.finalize();
+
+            let decoder = Decoder::with_version(version);
+            assert_eq!(decoder.decode(&compressed, pgn_moves.len())?, pgn_moves);
+            assert_eq!(
+                decoder.decode_to_positions(&compressed, pgn_moves.len())?,
+                expected
+            );
+
+            // A prefix can be decoded without reading the whole game
+            let moves = decoder
+                .decode_iter(&compressed, pgn_moves.len())
+                .take(4)
+                .collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(moves.len(), 4);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_iter_stops_after_error() {
+        let decoder = Decoder::new();
+        let data = BitVec::from_elem(1, true);
+        let mut moves = decoder.decode_iter(&data, 10);
+        assert!(matches!(
+            moves.next(),
+            Some(Err(EncoderError::InvalidMoveIndex { .. }))
+        ));
+        assert!(moves.next().is_none());
+    }
+
+    #[test]
+    fn test_book_node_with_few_plies() -> Result<(), EncoderError> {
+        use crate::outcome::{GameResult, Termination};
+
+        let outcome = GameOutcome::new(GameResult::WhiteWins, Termination::Abandoned);
+        for version in [CodecVersion::V3, CodecVersion::V4] {
+            let decoder = Decoder::with_version(version);
+
+            // A game without moves is the book root followed by the trailer
+            let data = Encoder::with_version(version).finalize_with_outcome(&outcome);
+            assert_eq!(
+                decoder.decode_with_outcome(&data, 0)?,
+                (Vec::new(), Some(outcome))
+            );
+
+            // A book line longer than the game means a wrong ply count
+            let (data, _) = encode(version, "e4 e5 Nf3")?;
+            assert!(matches!(
+                decoder.decode(&data, 2),
+                Err(EncoderError::InvalidOpeningNode { .. })
+            ));
+        }
+        Ok(())
+    }
+
+    fn encode(version: CodecVersion, moves: &str) -> Result<(BitVec, usize), EncoderError> {
+        let mut encoder = Encoder::with_version(version);
+        for mv in moves.split_whitespace() {
+            encoder.encode_move(mv)?;
+        }
+        Ok((encoder.finalize(), encoder.plies()))
+    }
+
+    fn encode_bytes(version: CodecVersion, moves: &str) -> Result<(Vec<u8>, usize), EncoderError> {
+        let (data, plies) = encode(version, moves)?;
+        Ok((data.to_bytes(), plies))
+    }
+
+    #[test]
+    fn test_common_prefix_and_starts_with() -> Result<(), EncoderError> {
+        let najdorf = "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Be3 e5 Nb3 Be6 f3 h5";
+        let dragon = "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6 Be3 Bg7 f3 O-O";
+        let line: Vec<String> = "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3"
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+
+        for version in [
+            CodecVersion::V1,
+            CodecVersion::V2,
+            CodecVersion::V3,
+            CodecVersion::V4,
+        ] {
+            let decoder = Decoder::with_version(version);
+            let (a, a_plies) = encode_bytes(version, najdorf)?;
+            let (b, b_plies) = encode_bytes(version, dragon)?;
+
+            assert_eq!(decoder.common_prefix_plies(&a, a_plies, &b, b_plies)?, 9);
+            let (c, c_plies) = encode_bytes(version, "e4 c5 Nf3 d6 d4")?;
+            assert_eq!(decoder.common_prefix_plies(&a, a_plies, &c, c_plies)?, 5);
+
+            assert!(decoder.starts_with(&a, a_plies, &line)?);
+            assert!(decoder.starts_with(&b, b_plies, &line)?);
+            assert!(decoder.starts_with(&a, a_plies, &[])?);
+            assert!(!decoder.starts_with(&a, 4, &line)?);
+
+            let mut sideline = line.clone();
+            sideline[8] = "Bc4".to_string();
+            assert!(!decoder.starts_with(&a, a_plies, &sideline)?);
+
+            // A line that cannot be played matches no game
+            sideline[8] = "Qh8".to_string();
+            assert!(!decoder.starts_with(&a, a_plies, &sideline)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_across_threads() -> Result<(), EncoderError> {
+        let pgn_moves = vec!["d4", "Nf6", "c4", "e6", "Nc3", "Bb4"];
+        let mut encoder = Encoder::new();
+        for mv in &pgn_moves {
+            encoder.encode_move(mv)?;
+        }
+        let compressed = encoder.finalize();
+
+        let decoder = Decoder::new();
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| scope.spawn(|| decoder.decode(&compressed, pgn_moves.len())))
+                .collect();
+            for handle in handles {
+                assert_eq!(handle.join().unwrap().unwrap(), pgn_moves);
+            }
+        });
+        Ok(())
+    }
+}
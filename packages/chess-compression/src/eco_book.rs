@@ -0,0 +1,14 @@
+// src/eco_book.rs
+
+use crate::opening_book::{Node, OpeningBook};
+use alloc::borrow::Cow;
+use shakmaty::{Move, Role, Square};
+
+// `ECO_BOOK`, the trie of `ECO_LINES`, generated by `build.rs`
+include!(concat!(env!("OUT_DIR"), "/eco_book.rs"));
+
+/// Retrieves the built-in book compiled from `ECO_LINES`.
+#[inline]
+pub fn eco_book() -> &'static OpeningBook {
+    &ECO_BOOK
+}
@@ -0,0 +1,311 @@
+// src/explorer.rs
+//! Opening explorer statistics computed by replaying compressed games.
+
+use crate::decoder::Decoder;
+use crate::outcome::GameResult;
+use crate::pgn_compress::{CodecVersion, EncoderError};
+use alloc::{string::String, string::ToString, vec::Vec};
+use bit_vec::BitVec;
+use core::cmp::Reverse;
+use shakmaty::{
+    san::SanPlus,
+    uci::UciMove,
+    zobrist::{Zobrist64, ZobristHash},
+    CastlingMode, Chess, EnPassantMode, Move, Position,
+};
+
+/// Game counts, results and ratings of the games that reached a position or
+/// played a move.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExplorerStats {
+    pub games: u32,
+    pub white: u32,
+    pub draws: u32,
+    pub black: u32,
+    elo_sum: u64,
+    rated_games: u32,
+}
+
+impl ExplorerStats {
+    /// Average rating over the games with at least one rated player, where
+    /// each game counts the mean rating of its rated players.
+    pub fn average_elo(&self) -> Option<i32> {
+        if self.rated_games == 0 {
+            return None;
+        }
+        Some((self.elo_sum / u64::from(self.rated_games)) as i32)
+    }
+
+    fn add(&mut self, result: GameResult, elo: Option<i32>) {
+        self.games += 1;
+        match result {
+            GameResult::WhiteWins => self.white += 1,
+            GameResult::Draw => self.draws += 1,
+            GameResult::BlackWins => self.black += 1,
+            GameResult::Unknown => {}
+        }
+        if let Some(elo) = elo {
+            self.elo_sum += elo as u64;
+            self.rated_games += 1;
+        }
+    }
+}
+
+/// A move played from the explored position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExplorerMove {
+    pub uci: String,
+    pub san: String,
+    pub stats: ExplorerStats,
+}
+
+/// The explored position and the moves played from it, most played first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExplorerTree {
+    /// Every game that reached the position, including games that ended there.
+    pub stats: ExplorerStats,
+    pub moves: Vec<ExplorerMove>,
+}
+
+/// A compressed game as read by `Explorer`.
+///
+/// The result is read from the game's outcome trailer, or is `result` for
+/// games stored without one, such as those of the games table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExplorerGame<'a> {
+    pub compressed: &'a [u8],
+    pub plies: usize,
+    /// Codec version the game was encoded with.
+    pub version: CodecVersion,
+    /// Result of a game without an outcome trailer. `Unknown` games count
+    /// towards `games` but not towards any result.
+    pub result: GameResult,
+    pub white_elo: Option<i32>,
+    pub black_elo: Option<i32>,
+}
+
+impl ExplorerGame<'_> {
+    fn elo(&self) -> Option<i32> {
+        match (self.white_elo, self.black_elo) {
+            (Some(white), Some(black)) => Some((white + black) / 2),
+            (elo, None) | (None, elo) => elo,
+        }
+    }
+}
+
+/// Collects the moves played from a position across compressed games.
+///
+/// Games are matched by position, so a game that transposes into the
+/// position counts the same as one that reached it by the usual move order.
+/// A game that reaches the position more than once counts once, with the
+/// move played the first time. Games of different codec versions can be mixed.
+pub struct Explorer {
+    position: Chess,
+    key: Zobrist64,
+    pieces: usize,
+    stats: ExplorerStats,
+    moves: Vec<(Move, ExplorerStats)>,
+}
+
+impl Explorer {
+    pub fn new(position: Chess) -> Self {
+        Explorer {
+            key: position.zobrist_hash(EnPassantMode::Legal),
+            pieces: position.board().occupied().count(),
+            position,
+            stats: ExplorerStats::default(),
+            moves: Vec::new(),
+        }
+    }
+
+    /// Replays a game and counts it if it reaches the position.
+    ///
+    /// Decoding stops once the game has fewer pieces than the position, since
+    /// captures cannot be undone, so games that leave the opening early are
+    /// cheap to skip.
+    pub fn add_game(&mut self, game: &ExplorerGame) -> Result<(), EncoderError> {
+        let data = BitVec::from_bytes(game.compressed);
+        let mut moves = Decoder::with_version(game.version).decode_iter(&data, game.plies);
+        loop {
+            let board = moves.position();
+            let pieces = board.board().occupied().count();
+            if pieces < self.pieces {
+                return Ok(());
+            }
+            if pieces == self.pieces
+                && board.turn() == self.position.turn()
+                && board.zobrist_hash::<Zobrist64>(EnPassantMode::Legal) == self.key
+            {
+                break;
+            }
+            if moves.next().transpose()?.is_none() {
+                return Ok(());
+            }
+        }
+
+        let next = moves.next().transpose()?;
+        // Skip the remaining moves to reach the outcome trailer
+        for mv in moves.by_ref() {
+            mv?;
+        }
+        let result = moves
+            .outcome()
+            .map_or(game.result, |outcome| outcome.result);
+        let elo = game.elo();
+
+        self.stats.add(result, elo);
+        if let Some(mv) = next {
+            let index = match self.moves.iter().position(|(played, _)| *played == mv) {
+                Some(index) => index,
+                None => {
+                    self.moves.push((mv, ExplorerStats::default()));
+                    self.moves.len() - 1
+                }
+            };
+            self.moves[index].1.add(result, elo);
+        }
+        Ok(())
+    }
+
+    /// Returns the collected statistics, with moves sorted by game count.
+    pub fn finish(self) -> ExplorerTree {
+        let mut moves: Vec<ExplorerMove> = self
+            .moves
+            .into_iter()
+            .map(|(mv, stats)| ExplorerMove {
+                uci: UciMove::from_move(&mv, CastlingMode::Standard).to_string(),
+                san: SanPlus::from_move(self.position.clone(), &mv).to_string(),
+                stats,
+            })
+            .collect();
+        // Stable, so moves with equal counts keep the order they were first seen in
+        moves.sort_by_key(|mv| Reverse(mv.stats.games));
+        ExplorerTree {
+            stats: self.stats,
+            moves,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outcome::{GameOutcome, Termination};
+    use crate::{compress_pgn, compress_pgn_with_outcome, compress_pgn_with_version};
+    use shakmaty::fen::Fen;
+
+    fn compress(moves: &str, result: Option<GameResult>) -> (Vec<u8>, usize) {
+        let moves: Vec<String> = moves.split_whitespace().map(String::from).collect();
+        let compressed = match result {
+            Some(result) => {
+                let outcome = GameOutcome::new(result, Termination::Unknown);
+                compress_pgn_with_outcome(&moves, &outcome).unwrap()
+            }
+            None => compress_pgn(&moves).unwrap(),
+        };
+        (compressed, moves.len())
+    }
+
+    fn game(
+        (compressed, plies): &(Vec<u8>, usize),
+        white_elo: Option<i32>,
+        black_elo: Option<i32>,
+    ) -> ExplorerGame<'_> {
+        ExplorerGame {
+            compressed,
+            plies: *plies,
+            version: CodecVersion::V1,
+            result: GameResult::Unknown,
+            white_elo,
+            black_elo,
+        }
+    }
+
+    #[test]
+    fn test_explorer_counts_next_moves() {
+        let games = [
+            (
+                compress("e4 e5 Nf3 Nc6", Some(GameResult::WhiteWins)),
+                Some(2400),
+                Some(2200),
+            ),
+            (
+                compress("e4 e5 Nf3 Nf6", Some(GameResult::Draw)),
+                Some(2000),
+                None,
+            ),
+            (
+                compress("e4 c5 Nf3 d6", Some(GameResult::BlackWins)),
+                None,
+                None,
+            ),
+            // Transposes into 1. e4 e5 2. Nf3
+            (
+                compress("Nf3 e5 e4 Nc6", Some(GameResult::BlackWins)),
+                Some(1800),
+                Some(1800),
+            ),
+            // Ends in the position, without a result
+            (compress("e4 e5 Nf3", None), None, None),
+            (
+                compress("d4 d5", Some(GameResult::Draw)),
+                Some(2800),
+                Some(2800),
+            ),
+        ];
+
+        let fen: Fen = "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+            .parse()
+            .unwrap();
+        let position: Chess = fen.into_position(CastlingMode::Standard).unwrap();
+        let mut explorer = Explorer::new(position);
+        for (compressed, white_elo, black_elo) in &games {
+            explorer
+                .add_game(&game(compressed, *white_elo, *black_elo))
+                .unwrap();
+        }
+
+        // Stored like the games table: another version, no trailer, result on the side
+        let moves: Vec<String> = ["e4", "e5", "Nf3", "Nf6"].map(String::from).into();
+        let v2 = (
+            compress_pgn_with_version(&moves, CodecVersion::V2).unwrap(),
+            4,
+        );
+        explorer
+            .add_game(&ExplorerGame {
+                version: CodecVersion::V2,
+                result: GameResult::WhiteWins,
+                ..game(&v2, None, None)
+            })
+            .unwrap();
+        // A trailer takes precedence over the given result
+        let with_trailer = compress("e4 e5 Nf3 Nc6", Some(GameResult::Draw));
+        explorer
+            .add_game(&ExplorerGame {
+                result: GameResult::BlackWins,
+                ..game(&with_trailer, None, None)
+            })
+            .unwrap();
+        let tree = explorer.finish();
+
+        assert_eq!(tree.stats.games, 6);
+        assert_eq!(
+            (tree.stats.white, tree.stats.draws, tree.stats.black),
+            (2, 2, 1)
+        );
+        assert_eq!(tree.stats.average_elo(), Some(2033));
+
+        let moves: Vec<_> = tree.moves.iter().map(|mv| mv.san.as_str()).collect();
+        assert_eq!(moves, ["Nc6", "Nf6"]);
+        let nc6 = &tree.moves[0];
+        assert_eq!(nc6.uci, "b8c6");
+        assert_eq!(nc6.stats.games, 3);
+        assert_eq!(
+            (nc6.stats.white, nc6.stats.draws, nc6.stats.black),
+            (1, 1, 1)
+        );
+        assert_eq!(nc6.stats.average_elo(), Some(2050));
+        let nf6 = &tree.moves[1];
+        assert_eq!((nf6.stats.games, nf6.stats.white), (2, 1));
+    }
+}
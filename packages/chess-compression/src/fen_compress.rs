@@ -1,4 +1,5 @@
 #![allow(dead_code)]
+use alloc::{boxed::Box, string::String, vec::Vec};
 use shakmaty::{CastlingMode, CastlingSide, Chess, Color, File, Position, Rank, Role, Square};
 use thiserror::Error;
 
@@ -13,9 +14,21 @@ pub enum CompressedPositionError {
     #[error("Data too short for packed_state")]
     InsufficientDataForPackedState,
     #[error("FEN parsing error: {0}")]
-    FenParseError(#[from] shakmaty::fen::ParseFenError),
+    FenParseError(shakmaty::fen::ParseFenError),
     #[error("Position conversion error: {0}")]
-    PositionConversionError(#[from] shakmaty::PositionError<Chess>),
+    PositionConversionError(Box<shakmaty::PositionError<Chess>>),
+}
+
+impl From<shakmaty::fen::ParseFenError> for CompressedPositionError {
+    fn from(error: shakmaty::fen::ParseFenError) -> Self {
+        CompressedPositionError::FenParseError(error)
+    }
+}
+
+impl From<shakmaty::PositionError<Chess>> for CompressedPositionError {
+    fn from(error: shakmaty::PositionError<Chess>) -> Self {
+        CompressedPositionError::PositionConversionError(Box::new(error))
+    }
 }
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct CompressedPosition {
@@ -114,9 +127,8 @@ impl CompressedPosition {
     }
 
     pub fn decompress(compressed: &[u8; 32]) -> Result<Chess, CompressedPositionError> {
-        println!("Decompressing...");
+        use core::fmt::Write;
         use shakmaty::fen::Fen;
-        use std::fmt::Write;
 
         let mut fen = String::new();
         let mut side_to_move = Color::White;
@@ -0,0 +1,231 @@
+// src/fingerprint.rs
+use crate::decoder::Decoder;
+use crate::outcome::GameResult;
+use crate::pgn_compress::{san_to_move, CodecVersion, EncoderError};
+use alloc::{string::String, string::ToString, vec::Vec};
+use bit_vec::BitVec;
+use core::fmt;
+use shakmaty::{uci::UciMove, CastlingMode, Chess, Move, Position};
+
+/// Stable 128-bit fingerprint of a game, used to detect duplicates across sources.
+///
+/// The fingerprint is computed from the moves in UCI notation, so it does not
+/// depend on SAN spelling or on the codec version a game was stored with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GameFingerprint(pub [u8; 16]);
+
+impl GameFingerprint {
+    pub fn to_bytes(&self) -> [u8; 16] {
+        self.0
+    }
+}
+
+impl fmt::Display for GameFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+// 128-bit FNV-1a, chosen because its output is fixed by specification
+const FNV_OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+#[derive(Clone)]
+struct Fnv128(u128);
+
+impl Fnv128 {
+    fn new() -> Self {
+        Fnv128(FNV_OFFSET_BASIS)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u128::from(byte);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    /// Writes a tagged, length-prefixed field so that fields cannot run together.
+    fn write_field(&mut self, tag: u8, bytes: &[u8]) {
+        self.write(&[tag]);
+        self.write(&(bytes.len() as u32).to_le_bytes());
+        self.write(bytes);
+    }
+}
+
+/// Builds a `GameFingerprint` from a game's moves and, optionally, its players,
+/// date and result.
+///
+/// Metadata is hashed in a fixed order after the moves, whatever order the
+/// setters are called in. Player names are trimmed and lowercased.
+#[derive(Clone)]
+pub struct Fingerprinter {
+    board: Chess,
+    moves: Fnv128,
+    plies: u32,
+    players: Option<(String, String)>,
+    date: Option<String>,
+    result: Option<GameResult>,
+}
+
+impl Default for Fingerprinter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fingerprinter {
+    pub fn new() -> Self {
+        Fingerprinter {
+            board: Chess::default(),
+            moves: Fnv128::new(),
+            plies: 0,
+            players: None,
+            date: None,
+            result: None,
+        }
+    }
+
+    /// Adds a move given in SAN.
+    pub fn push_san(&mut self, san: &str) -> Result<(), EncoderError> {
+        let mv = san_to_move(&self.board, self.plies as usize, san)?;
+        self.push_legal_move(&mv);
+        Ok(())
+    }
+
+    /// Adds a move that must be legal in the current position.
+    pub fn push_legal_move(&mut self, mv: &Move) {
+        let uci = UciMove::from_move(mv, CastlingMode::Standard).to_string();
+        self.moves.write_field(b'm', uci.as_bytes());
+        self.plies += 1;
+        self.board.play_unchecked(mv);
+    }
+
+    pub fn players(&mut self, white: &str, black: &str) -> &mut Self {
+        self.players = Some((normalize_name(white), normalize_name(black)));
+        self
+    }
+
+    /// Sets the game date, as written in the PGN `Date` tag.
+    pub fn date(&mut self, date: &str) -> &mut Self {
+        self.date = Some(date.trim().to_string());
+        self
+    }
+
+    pub fn result(&mut self, result: GameResult) -> &mut Self {
+        self.result = Some(result);
+        self
+    }
+
+    pub fn finish(&self) -> GameFingerprint {
+        let mut hash = self.moves.clone();
+        hash.write_field(b'n', &self.plies.to_le_bytes());
+        if let Some((white, black)) = &self.players {
+            hash.write_field(b'w', white.as_bytes());
+            hash.write_field(b'b', black.as_bytes());
+        }
+        if let Some(date) = &self.date {
+            hash.write_field(b'd', date.as_bytes());
+        }
+        if let Some(result) = self.result {
+            hash.write_field(b'r', result.as_str().as_bytes());
+        }
+        GameFingerprint(hash.0.to_be_bytes())
+    }
+}
+
+fn normalize_name(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Fingerprints a game given as SAN moves, without metadata.
+pub fn fingerprint_moves(moves: &[String]) -> Result<GameFingerprint, EncoderError> {
+    let mut fingerprinter = Fingerprinter::new();
+    for mv in moves {
+        fingerprinter.push_san(mv)?;
+    }
+    Ok(fingerprinter.finish())
+}
+
+/// Fingerprints a compressed game, without metadata.
+///
+/// Games stored with different codec versions get the same fingerprint.
+pub fn fingerprint_compressed(
+    compressed: &[u8],
+    plies: usize,
+    version: CodecVersion,
+) -> Result<GameFingerprint, EncoderError> {
+    let mut fingerprinter = Fingerprinter::new();
+    for mv in Decoder::with_version(version).decode_iter(&BitVec::from_bytes(compressed), plies) {
+        fingerprinter.push_legal_move(&mv?);
+    }
+    Ok(fingerprinter.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compress_pgn_with_version;
+
+    fn moves(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_fingerprint_is_stable() {
+        let fingerprint = fingerprint_moves(&moves("e4 e5 Nf3 Nc6")).unwrap();
+        // Stored in the database, so the value must never change
+        assert_eq!(fingerprint.to_string(), "8a7bc8d105c29f1b7118697063e801cb");
+        assert_eq!(
+            fingerprint,
+            fingerprint_moves(&moves("e4 e5 Ngf3 Nbc6")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_codec_version() -> Result<(), EncoderError> {
+        let game = moves("d4 Nf6 c4 e6 Nc3 Bb4 Qc2 O-O a3 Bxc3+ Qxc3 b6");
+        let expected = fingerprint_moves(&game)?;
+        for version in [
+            CodecVersion::V1,
+            CodecVersion::V2,
+            CodecVersion::V3,
+            CodecVersion::V4,
+        ] {
+            let compressed = compress_pgn_with_version(&game, version)?;
+            assert_eq!(
+                fingerprint_compressed(&compressed, game.len(), version)?,
+                expected
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_fingerprint_distinguishes_games_and_metadata() {
+        let a = fingerprint_moves(&moves("e4 e5")).unwrap();
+        let b = fingerprint_moves(&moves("e4 e6")).unwrap();
+        assert_ne!(a, b);
+
+        let mut with_players = Fingerprinter::new();
+        with_players.push_san("e4").unwrap();
+        with_players.push_san("e5").unwrap();
+        let plain = with_players.finish();
+        with_players.players("Carlsen, Magnus", "Nakamura, Hikaru");
+        assert_ne!(plain, with_players.finish());
+
+        let mut normalized = with_players.clone();
+        normalized.players(" carlsen,  Magnus", "NAKAMURA, Hikaru");
+        assert_eq!(with_players.finish(), normalized.finish());
+
+        let mut swapped = with_players.clone();
+        swapped.players("Nakamura, Hikaru", "Carlsen, Magnus");
+        assert_ne!(with_players.finish(), swapped.finish());
+    }
+}
@@ -0,0 +1,478 @@
+// src/huffman.rs
+//! Huffman codebooks with table-driven decoding.
+//!
+//! A canonical codebook is fully described by the code length of each
+//! symbol, which is how it is serialized: codes are handed out in order of
+//! length, then symbol. Decoding looks up `TABLE_BITS` bits at a time instead
+//! of walking a tree one bit at a time.
+
+use alloc::borrow::Cow;
+use alloc::collections::{BTreeMap, BinaryHeap};
+use alloc::{vec, vec::Vec};
+use bit_vec::BitVec;
+use core::cmp::Reverse;
+use thiserror::Error;
+
+/// Longest code a codebook can hold, in bits.
+pub const MAX_CODE_LEN: u8 = 32;
+
+// Most bits looked up per decoding step, enough for the move indices of
+// nearly every move. Longer codes chain into subtables, each only as wide as
+// the longest code below it needs, which keeps the static tables small.
+const TABLE_BITS: u32 = 8;
+
+/// Errors raised while building a codebook or encoding with it.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum HuffmanError {
+    #[error("Symbol {0} is not in the codebook")]
+    UnknownSymbol(u32),
+    #[error("Code of symbol {symbol} is {len} bits long, more than 32")]
+    CodeTooLong { symbol: u32, len: usize },
+    #[error("Code lengths do not form a prefix code")]
+    Oversubscribed,
+}
+
+// Code word read most significant bit first. A length of 0 means no code.
+// Visible to the crate for the tables `build.rs` generates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Code {
+    pub(crate) bits: u32,
+    pub(crate) len: u8,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Entry {
+    Invalid,
+    Symbol { symbol: u32, len: u8 },
+    Subtable { start: u32, bits: u8 },
+}
+
+/// A prefix code over `u32` symbols.
+#[derive(Debug, Clone)]
+pub struct Codebook {
+    // Indexed by symbol
+    codes: Cow<'static, [Code]>,
+    // First-level table followed by the subtables of longer codes
+    table: Cow<'static, [Entry]>,
+    // Bits looked up in the first-level table
+    root_bits: u8,
+}
+
+impl Codebook {
+    /// Wraps tables generated from `codes`, `table` and `root_bits` of a built
+    /// codebook, so that static codebooks need no initialization.
+    pub(crate) const fn from_static(
+        codes: &'static [Code],
+        table: &'static [Entry],
+        root_bits: u8,
+    ) -> Self {
+        Codebook {
+            codes: Cow::Borrowed(codes),
+            table: Cow::Borrowed(table),
+            root_bits,
+        }
+    }
+
+    /// Returns the codes, decoding table and first-level bits, for `build.rs`
+    /// to write out.
+    #[allow(dead_code)] // Used by `build.rs`
+    pub(crate) fn parts(&self) -> (&[Code], &[Entry], u8) {
+        (&self.codes, &self.table, self.root_bits)
+    }
+
+    /// Builds the canonical codebook with the given code length per symbol.
+    ///
+    /// `lengths[s]` is the length of the code of symbol `s`, or 0 if the
+    /// symbol has no code.
+    pub fn from_code_lengths(lengths: &[u8]) -> Result<Self, HuffmanError> {
+        let mut kraft = 0u64;
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len > MAX_CODE_LEN {
+                return Err(HuffmanError::CodeTooLong {
+                    symbol: symbol as u32,
+                    len: len.into(),
+                });
+            }
+            if len > 0 {
+                kraft += 1 << (MAX_CODE_LEN - len);
+            }
+        }
+        if kraft > 1 << MAX_CODE_LEN {
+            return Err(HuffmanError::Oversubscribed);
+        }
+
+        let mut order: Vec<usize> = (0..lengths.len()).filter(|&s| lengths[s] > 0).collect();
+        order.sort_by_key(|&s| (lengths[s], s));
+
+        let mut codes = vec![Code::default(); lengths.len()];
+        let (mut next, mut prev_len) = (0u64, 0);
+        for symbol in order {
+            let len = lengths[symbol];
+            next <<= len - prev_len;
+            codes[symbol] = Code {
+                bits: next as u32,
+                len,
+            };
+            next += 1;
+            prev_len = len;
+        }
+        Ok(Self::from_codes(codes))
+    }
+
+    /// Builds the canonical Huffman codebook for the given symbol weights.
+    ///
+    /// Symbols must be distinct. Symbols with zero weight still get a code.
+    pub fn from_weights<I>(weights: I) -> Result<Self, HuffmanError>
+    where
+        I: IntoIterator<Item = (u32, u64)>,
+    {
+        let tree = HuffmanTree::build(weights);
+        let mut lengths = vec![0u8; tree.symbol_count()];
+        for (symbol, depth, _) in tree.leaves() {
+            lengths[symbol as usize] = checked_len(symbol, depth)?;
+        }
+        Self::from_code_lengths(&lengths)
+    }
+
+    /// Builds the Huffman codebook for the given weights with the codes read
+    /// off the tree, as `huffman-compress` 0.6 assigned them.
+    ///
+    /// The V1 to V3 game formats are frozen to these codes. They are not
+    /// canonical, so `code_lengths` does not describe them.
+    #[allow(dead_code)] // Used by `build.rs`
+    pub(crate) fn from_weights_tree_order<I>(weights: I) -> Result<Self, HuffmanError>
+    where
+        I: IntoIterator<Item = (u32, u64)>,
+    {
+        let tree = HuffmanTree::build(weights);
+        let mut codes = vec![Code::default(); tree.symbol_count()];
+        for (symbol, depth, bits) in tree.leaves() {
+            codes[symbol as usize] = Code {
+                bits,
+                len: checked_len(symbol, depth)?,
+            };
+        }
+        Ok(Self::from_codes(codes))
+    }
+
+    /// Returns the code length of each symbol, 0 for symbols without a code.
+    ///
+    /// This is the serialized form of a canonical codebook.
+    pub fn code_lengths(&self) -> Vec<u8> {
+        self.codes.iter().map(|code| code.len).collect()
+    }
+
+    /// Returns the length of the code of `symbol`, if it has one.
+    pub fn code_len(&self, symbol: u32) -> Option<u8> {
+        self.code(symbol).map(|code| code.len)
+    }
+
+    /// Appends the code of `symbol` to `buffer`.
+    pub fn encode(&self, buffer: &mut BitVec, symbol: u32) -> Result<(), HuffmanError> {
+        let code = self
+            .code(symbol)
+            .ok_or(HuffmanError::UnknownSymbol(symbol))?;
+        for i in (0..code.len).rev() {
+            buffer.push((code.bits >> i) & 1 == 1);
+        }
+        Ok(())
+    }
+
+    /// Reads one symbol, or returns `None` if the bits do not start with a
+    /// complete code.
+    pub fn decode(&self, bits: &mut BitReader<'_>) -> Option<u32> {
+        let (mut start, mut width) = (0, u32::from(self.root_bits));
+        loop {
+            let index = start + bits.peek(width) as usize;
+            match self.table[index] {
+                Entry::Symbol { symbol, len } => {
+                    return bits.consume(len.into()).then_some(symbol);
+                }
+                Entry::Subtable {
+                    start: next,
+                    bits: next_width,
+                } => {
+                    if !bits.consume(width) {
+                        return None;
+                    }
+                    start = next as usize;
+                    width = next_width.into();
+                }
+                Entry::Invalid => return None,
+            }
+        }
+    }
+
+    fn code(&self, symbol: u32) -> Option<Code> {
+        self.codes
+            .get(symbol as usize)
+            .copied()
+            .filter(|code| code.len > 0)
+    }
+
+    // Codes must form a prefix code
+    fn from_codes(codes: Vec<Code>) -> Self {
+        let coded: Vec<(u32, Code)> = (0u32..)
+            .zip(codes.iter().copied())
+            .filter(|(_, code)| code.len > 0)
+            .collect();
+        let root_bits = table_width(&coded, 0);
+        let mut table = vec![Entry::Invalid; 1 << root_bits];
+        fill_table(&mut table, 0, root_bits, &coded, 0);
+        Codebook {
+            codes: Cow::Owned(codes),
+            table: Cow::Owned(table),
+            root_bits: root_bits as u8,
+        }
+    }
+}
+
+// Bits a table needs for codes whose first `consumed` bits lead to it
+fn table_width(codes: &[(u32, Code)], consumed: u32) -> u32 {
+    let longest = codes.iter().map(|(_, code)| u32::from(code.len)).max();
+    longest.map_or(1, |len| (len - consumed).clamp(1, TABLE_BITS))
+}
+
+// Fills the table of `width` bits at `start` with `codes`, whose first
+// `consumed` bits lead to it, adding subtables for the longer ones
+fn fill_table(
+    table: &mut Vec<Entry>,
+    start: usize,
+    width: u32,
+    codes: &[(u32, Code)],
+    consumed: u32,
+) {
+    let mut longer: BTreeMap<usize, Vec<(u32, Code)>> = BTreeMap::new();
+    for &(symbol, code) in codes {
+        let remaining = u32::from(code.len) - consumed;
+        let step = remaining.min(width);
+        // The reader returns the first bit lowest, so chunks are reversed
+        let chunk = (code.bits >> (remaining - step)).reverse_bits() >> (32 - step);
+        let chunk = chunk as usize;
+        if remaining > width {
+            longer.entry(chunk).or_default().push((symbol, code));
+            continue;
+        }
+        for pad in 0..1 << (width - step) {
+            table[start + (chunk | pad << step)] = Entry::Symbol {
+                symbol,
+                len: step as u8,
+            };
+        }
+    }
+    for (chunk, codes) in longer {
+        let consumed = consumed + width;
+        let bits = table_width(&codes, consumed);
+        let next = table.len();
+        table.resize(next + (1 << bits), Entry::Invalid);
+        table[start + chunk] = Entry::Subtable {
+            start: next as u32,
+            bits: bits as u8,
+        };
+        fill_table(table, next, bits, &codes, consumed);
+    }
+}
+
+fn checked_len(symbol: u32, depth: usize) -> Result<u8, HuffmanError> {
+    if depth > MAX_CODE_LEN as usize {
+        return Err(HuffmanError::CodeTooLong { symbol, len: depth });
+    }
+    Ok(depth as u8)
+}
+
+enum Node {
+    Leaf(u32),
+    Branch(usize, usize),
+}
+
+struct HuffmanTree {
+    nodes: Vec<Node>,
+    root: Option<usize>,
+}
+
+impl HuffmanTree {
+    // Repeatedly merges the two lightest nodes. Ties go to the node with the
+    // larger smallest symbol, then to the later node.
+    fn build<I>(weights: I) -> Self
+    where
+        I: IntoIterator<Item = (u32, u64)>,
+    {
+        let mut nodes = Vec::new();
+        let mut heap = BinaryHeap::new();
+        for (symbol, weight) in weights {
+            heap.push((Reverse(weight), symbol, nodes.len()));
+            nodes.push(Node::Leaf(symbol));
+        }
+        let root = loop {
+            let Some((Reverse(weight), symbol, left)) = heap.pop() else {
+                break None;
+            };
+            let Some((Reverse(right_weight), right_symbol, right)) = heap.pop() else {
+                break Some(left);
+            };
+            heap.push((
+                Reverse(weight.saturating_add(right_weight)),
+                symbol.min(right_symbol),
+                nodes.len(),
+            ));
+            nodes.push(Node::Branch(left, right));
+        };
+        HuffmanTree { nodes, root }
+    }
+
+    fn symbol_count(&self) -> usize {
+        self.nodes
+            .iter()
+            .filter_map(|node| match node {
+                Node::Leaf(symbol) => Some(*symbol as usize + 1),
+                Node::Branch(..) => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    // Symbol, depth and code of every leaf. The first node of each merge
+    // takes a 1 bit, and a lone symbol gets the code 0.
+    fn leaves(&self) -> Vec<(u32, usize, u32)> {
+        let mut leaves = Vec::new();
+        let mut stack: Vec<_> = self.root.map(|root| (root, 0, 0u64)).into_iter().collect();
+        while let Some((node, depth, bits)) = stack.pop() {
+            match self.nodes[node] {
+                Node::Leaf(symbol) => leaves.push((symbol, depth.max(1), bits as u32)),
+                Node::Branch(left, right) => {
+                    stack.push((right, depth + 1, bits << 1));
+                    stack.push((left, depth + 1, (bits << 1) | 1));
+                }
+            }
+        }
+        leaves
+    }
+}
+
+/// Reads bits from a `BitVec`, several at a time when decoding symbols.
+#[derive(Debug, Clone)]
+pub struct BitReader<'a> {
+    blocks: &'a [u32],
+    pos: usize,
+    len: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(data: &'a BitVec) -> Self {
+        BitReader {
+            blocks: data.storage(),
+            pos: 0,
+            len: data.len(),
+        }
+    }
+
+    // Next `count` bits, with the first bit lowest. Bits past the end are
+    // unspecified, so callers check lengths with `consume`.
+    #[inline]
+    fn peek(&self, count: u32) -> u32 {
+        // Bit `i` of a `BitVec` is bit `i % 32` of storage block `i / 32`
+        let block = self.pos / 32;
+        let low = u64::from(self.blocks.get(block).copied().unwrap_or(0));
+        let high = u64::from(self.blocks.get(block + 1).copied().unwrap_or(0));
+        let window = (high << 32 | low) >> (self.pos % 32);
+        (window & ((1 << count) - 1)) as u32
+    }
+
+    // Skips `count` bits, or every remaining bit if fewer are left
+    #[inline]
+    fn consume(&mut self, count: u32) -> bool {
+        let fits = count as usize <= self.len - self.pos;
+        self.pos = if fits {
+            self.pos + count as usize
+        } else {
+            self.len
+        };
+        fits
+    }
+}
+
+impl Iterator for BitReader<'_> {
+    type Item = bool;
+
+    fn next(&mut self) -> Option<bool> {
+        if self.pos >= self.len {
+            return None;
+        }
+        let bit = (self.blocks[self.pos / 32] >> (self.pos % 32)) & 1 == 1;
+        self.pos += 1;
+        Some(bit)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.len - self.pos;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for BitReader<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bits(s: &str) -> BitVec {
+        s.chars().map(|c| c == '1').collect()
+    }
+
+    fn decode_all(codebook: &Codebook, data: &BitVec) -> Vec<u32> {
+        let mut reader = BitReader::new(data);
+        std::iter::from_fn(|| codebook.decode(&mut reader)).collect()
+    }
+
+    #[test]
+    fn test_canonical_codes() -> Result<(), HuffmanError> {
+        let codebook = Codebook::from_code_lengths(&[2, 1, 3, 3, 0])?;
+        let mut buffer = BitVec::new();
+        for symbol in [0, 1, 2, 3] {
+            codebook.encode(&mut buffer, symbol)?;
+        }
+        assert_eq!(buffer, bits("100110111"));
+        assert_eq!(decode_all(&codebook, &buffer), [0, 1, 2, 3]);
+        assert_eq!(
+            codebook.encode(&mut buffer, 4),
+            Err(HuffmanError::UnknownSymbol(4))
+        );
+        assert_eq!(
+            Codebook::from_code_lengths(&[1, 1, 1]).unwrap_err(),
+            HuffmanError::Oversubscribed
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_long_codes_round_trip() -> Result<(), HuffmanError> {
+        // Fibonacci weights give the deepest possible tree
+        let mut weights = vec![(0, 1), (1, 1)];
+        for symbol in 2..30 {
+            let weight = weights[symbol - 1].1 + weights[symbol - 2].1;
+            weights.push((symbol as u32, weight));
+        }
+        let symbols: Vec<u32> = (0..30).chain((0..30).rev()).collect();
+
+        let canonical = Codebook::from_weights(weights.iter().copied())?;
+        let tree_order = Codebook::from_weights_tree_order(weights.iter().copied())?;
+        assert_eq!(canonical.code_len(0), Some(29));
+        assert_eq!(canonical.code_lengths(), tree_order.code_lengths());
+
+        for codebook in [&canonical, &tree_order] {
+            let mut buffer = BitVec::new();
+            for &symbol in &symbols {
+                codebook.encode(&mut buffer, symbol)?;
+            }
+            assert_eq!(decode_all(codebook, &buffer), symbols);
+
+            // A truncated code is not decoded
+            buffer.truncate(buffer.len() - 1);
+            assert_eq!(decode_all(codebook, &buffer).len(), symbols.len() - 1);
+        }
+
+        let restored = Codebook::from_code_lengths(&canonical.code_lengths())?;
+        assert_eq!(restored.codes, canonical.codes);
+        Ok(())
+    }
+}
@@ -2,20 +2,63 @@
 //!
 //! This library provides functionality for compressing and decompressing
 //! chess positions and games.
+//!
+//! The `wasm` feature adds the JS bindings in `wasm`, which
+//! `chess-compression-wasm` enables to build them into a module. Native
+//! users do not pull in `wasm-bindgen` and `js-sys`.
+//!
+//! Without the `std` feature, on by default, the crate is `no_std` and only
+//! needs `alloc`. PGN import is not available then.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
 
+pub mod decoder;
+mod eco_book;
+pub mod eval;
+pub mod explorer;
 pub mod fen_compress;
+pub mod fingerprint;
+mod frequencies;
+pub mod huffman;
 mod huffman_code;
+pub mod opening_book;
+pub mod outcome;
 pub mod pgn_compress;
+pub mod pgn_document;
+#[cfg(feature = "std")]
+pub mod pgn_import;
 mod psqt;
+mod ranking;
+pub mod transcode;
+#[cfg(feature = "wasm")]
 pub mod wasm;
+#[cfg(feature = "wasm")]
 pub use wasm::*;
 
+pub use decoder::{DecodeIter, Decoder};
+pub use eco_book::eco_book;
+pub use explorer::{Explorer, ExplorerGame, ExplorerMove, ExplorerStats, ExplorerTree};
 pub use fen_compress::{CompressedPosition, CompressedPositionError};
-pub use pgn_compress::{Encoder, EncoderError};
+pub use fingerprint::{fingerprint_compressed, fingerprint_moves, Fingerprinter, GameFingerprint};
+pub use frequencies::{fit_phase_codes, MOVE_COUNT_BOUNDS};
+pub use huffman::{BitReader, Codebook, HuffmanError};
+pub use opening_book::{OpeningBook, OpeningBookError};
+pub use outcome::{GameOutcome, GameResult, Termination};
+pub use pgn_compress::{CodecVersion, Encoder, EncoderError, ErrorContext};
+pub use pgn_document::{CompressedPgn, CompressedPgnError, PgnGame};
+#[cfg(feature = "std")]
+pub use pgn_import::{import_games, ImportError, ImportedGame, Importer};
+pub use transcode::{
+    transcode_compressed, transcode_moves, SampledPosition, TranscodeOptions, TranscodedGame,
+};
 
 // Re-export types from shakmaty that are used in our public API
 pub use shakmaty::{Chess, Position};
 
+use alloc::{string::String, vec::Vec};
+
 /// Compress a chess position
 ///
 /// This function takes a `Chess` position and returns a `CompressedPosition`.
@@ -65,17 +108,94 @@ pub fn decompress_position(compressed: &[u8; 32]) -> Result<Chess, CompressedPos
 /// let compressed = compress_pgn(&moves).unwrap();
 /// ```
 pub fn compress_pgn(moves: &[String]) -> Result<Vec<u8>, EncoderError> {
-    let mut encoder = Encoder::new();
+    compress_pgn_with_version(moves, CodecVersion::default())
+}
+
+/// Compress a sequence of chess moves (PGN) with a specific codec version
+///
+/// The version is not recorded in the output, so the same version must be
+/// passed to `decompress_pgn_with_version`.
+///
+/// # Examples
+///
+/// ```
+/// use chess_compression::{compress_pgn_with_version, CodecVersion};
+///
+/// let moves = vec!["e4".to_string(), "e5".to_string(), "Nf3".to_string(), "Nc6".to_string()];
+/// let compressed = compress_pgn_with_version(&moves, CodecVersion::V2).unwrap();
+/// ```
+pub fn compress_pgn_with_version(
+    moves: &[String],
+    version: CodecVersion,
+) -> Result<Vec<u8>, EncoderError> {
+    let mut encoder = Encoder::with_version(version);
     for move_str in moves {
         encoder.encode_move(move_str)?;
     }
     Ok(encoder.finalize().to_bytes())
 }
 
+/// Compress a sequence of chess moves (PGN) followed by the game's outcome
+///
+/// The result and termination are stored as a one-byte trailer after the
+/// moves. `decompress_pgn` still returns only the moves; `decompress_pgn_movetext`
+/// ends the movetext with the result, and `Decoder::decode_with_outcome` reads
+/// the whole outcome.
+///
+/// # Examples
+///
+/// ```
+/// use bit_vec::BitVec;
+/// use chess_compression::{compress_pgn_with_outcome, decompress_pgn, Decoder, GameOutcome, GameResult, Termination};
+///
+/// let moves = vec!["f3".to_string(), "e5".to_string(), "g4".to_string(), "Qh4#".to_string()];
+/// let outcome = GameOutcome::new(GameResult::BlackWins, Termination::Checkmate);
+/// let compressed = compress_pgn_with_outcome(&moves, &outcome).unwrap();
+/// assert_eq!(decompress_pgn(&compressed, moves.len()).unwrap(), moves);
+///
+/// let data = BitVec::from_bytes(&compressed);
+/// let (decoded, decoded_outcome) = Decoder::new().decode_with_outcome(&data, moves.len()).unwrap();
+/// assert_eq!(decoded, moves);
+/// assert_eq!(decoded_outcome, Some(outcome));
+/// ```
+pub fn compress_pgn_with_outcome(
+    moves: &[String],
+    outcome: &GameOutcome,
+) -> Result<Vec<u8>, EncoderError> {
+    compress_pgn_with_version_and_outcome(moves, CodecVersion::default(), outcome)
+}
+
+/// Compress a sequence of chess moves (PGN) with a specific codec version,
+/// followed by the game's outcome as `compress_pgn_with_outcome` does
+///
+/// # Examples
+///
+/// ```
+/// use chess_compression::{compress_pgn_with_version_and_outcome, decompress_pgn_movetext_with_version, CodecVersion, GameOutcome, GameResult, Termination};
+///
+/// let moves = vec!["f3".to_string(), "e5".to_string(), "g4".to_string(), "Qh4#".to_string()];
+/// let outcome = GameOutcome::new(GameResult::BlackWins, Termination::Checkmate);
+/// let compressed = compress_pgn_with_version_and_outcome(&moves, CodecVersion::V1, &outcome).unwrap();
+/// let movetext = decompress_pgn_movetext_with_version(&compressed, moves.len(), CodecVersion::V1).unwrap();
+/// assert_eq!(movetext, "f3 e5 g4 Qh4# 0-1");
+/// ```
+pub fn compress_pgn_with_version_and_outcome(
+    moves: &[String],
+    version: CodecVersion,
+    outcome: &GameOutcome,
+) -> Result<Vec<u8>, EncoderError> {
+    let mut encoder = Encoder::with_version(version);
+    for move_str in moves {
+        encoder.encode_move(move_str)?;
+    }
+    Ok(encoder.finalize_with_outcome(outcome).to_bytes())
+}
+
 /// Decompress a sequence of chess moves (PGN)
 ///
 /// This function takes a slice of compressed bytes and the number of plies,
-/// and returns a `Result<Vec<String>, EncoderError>`.
+/// and returns a `Result<Vec<String>, EncoderError>` holding only the moves,
+/// whether or not the game was stored with an outcome.
 ///
 /// # Examples
 ///
@@ -88,6 +208,61 @@ pub fn compress_pgn(moves: &[String]) -> Result<Vec<u8>, EncoderError> {
 /// assert_eq!(moves, decompressed);
 /// ```
 pub fn decompress_pgn(compressed: &[u8], plies: usize) -> Result<Vec<String>, EncoderError> {
-    let encoder = Encoder::new();
-    encoder.decode(&bit_vec::BitVec::from_bytes(compressed), plies)
+    decompress_pgn_with_version(compressed, plies, CodecVersion::default())
+}
+
+/// Decompress a game into PGN movetext, ending with its result
+///
+/// The moves are separated by spaces and followed by `1-0`, `0-1`, `1/2-1/2`
+/// or `*` when the game was stored with an outcome, as by
+/// `compress_pgn_with_outcome`. A game stored without one yields the moves
+/// alone.
+///
+/// # Examples
+///
+/// ```
+/// use chess_compression::{compress_pgn_with_outcome, decompress_pgn_movetext, GameOutcome, GameResult, Termination};
+///
+/// let moves = vec!["f3".to_string(), "e5".to_string(), "g4".to_string(), "Qh4#".to_string()];
+/// let outcome = GameOutcome::new(GameResult::BlackWins, Termination::Checkmate);
+/// let compressed = compress_pgn_with_outcome(&moves, &outcome).unwrap();
+/// assert_eq!(decompress_pgn_movetext(&compressed, moves.len()).unwrap(), "f3 e5 g4 Qh4# 0-1");
+/// ```
+pub fn decompress_pgn_movetext(compressed: &[u8], plies: usize) -> Result<String, EncoderError> {
+    decompress_pgn_movetext_with_version(compressed, plies, CodecVersion::default())
+}
+
+/// Decompress a game encoded with a specific codec version into PGN movetext,
+/// ending with its result as `decompress_pgn_movetext` does
+pub fn decompress_pgn_movetext_with_version(
+    compressed: &[u8],
+    plies: usize,
+    version: CodecVersion,
+) -> Result<String, EncoderError> {
+    let data = bit_vec::BitVec::from_bytes(compressed);
+    let (mut moves, outcome) = Decoder::with_version(version).decode_with_outcome(&data, plies)?;
+    if let Some(outcome) = outcome {
+        moves.push(outcome.result.as_str().into());
+    }
+    Ok(moves.join(" "))
+}
+
+/// Decompress a sequence of chess moves (PGN) encoded with a specific codec version
+///
+/// # Examples
+///
+/// ```
+/// use chess_compression::{compress_pgn_with_version, decompress_pgn_with_version, CodecVersion};
+///
+/// let moves = vec!["e4".to_string(), "e5".to_string(), "Nf3".to_string(), "Nc6".to_string()];
+/// let compressed = compress_pgn_with_version(&moves, CodecVersion::V2).unwrap();
+/// let decompressed = decompress_pgn_with_version(&compressed, moves.len(), CodecVersion::V2).unwrap();
+/// assert_eq!(moves, decompressed);
+/// ```
+pub fn decompress_pgn_with_version(
+    compressed: &[u8],
+    plies: usize,
+    version: CodecVersion,
+) -> Result<Vec<String>, EncoderError> {
+    Decoder::with_version(version).decode(&bit_vec::BitVec::from_bytes(compressed), plies)
 }
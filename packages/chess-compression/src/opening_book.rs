@@ -0,0 +1,529 @@
+// src/opening_book.rs
+use alloc::borrow::Cow;
+use alloc::{collections::BTreeMap, string::String, string::ToString, vec, vec::Vec};
+use shakmaty::{
+    san::{San, SanError},
+    Chess, Move, Position,
+};
+use thiserror::Error;
+
+/// Main lines of the ECO classification, used to build the opening book of
+/// `CodecVersion::V3`.
+///
+/// Node IDs depend on the order and content of these lines, so this table is
+/// frozen: a different book needs a new codec version. `build.rs` builds them
+/// into the static book returned by `eco_book`.
+#[allow(dead_code)] // Used by `build.rs`
+pub(crate) static ECO_LINES: &[&str] = &[
+    // Flank and irregular openings (A00-A09)
+    "g3 d5 Bg2",
+    "b3 e5 Bb2 Nc6 e3",
+    "b4 e5 Bb2 Bxb4 Bxe5 Nf6",
+    "f4 d5 Nf3 Nf6 e3 g6",
+    "f4 e5 fxe5 d6 exd6 Bxd6",
+    "Nf3 d5 g3 Nf6 Bg2 c6 O-O Bg4",
+    "Nf3 d5 c4 d4",
+    "Nf3 d5 c4 e6 g3 Nf6 Bg2 Be7 O-O O-O",
+    "Nf3 Nf6 g3 g6 Bg2 Bg7 O-O O-O d3 d6",
+    "Nf3 c5 g3 Nc6 Bg2 g6 O-O Bg7",
+    // English (A10-A39)
+    "c4 e5 Nc3 Nf6 Nf3 Nc6 g3 d5 cxd5 Nxd5 Bg2 Nb6 O-O Be7",
+    "c4 e5 Nc3 Nc6 g3 g6 Bg2 Bg7 d3 d6 e4",
+    "c4 e5 g3 Nf6 Bg2 d5 cxd5 Nxd5 Nc3 Nb6",
+    "c4 Nf6 Nc3 e6 e4 d5 e5 d4",
+    "c4 Nf6 Nc3 e5 Nf3 Nc6 e3 Bb4",
+    "c4 Nf6 Nc3 g6 g3 Bg7 Bg2 O-O Nf3 d6 O-O",
+    "c4 Nf6 g3 e6 Bg2 d5 Nf3 Be7 O-O O-O",
+    "c4 c5 Nc3 Nc6 g3 g6 Bg2 Bg7 Nf3 Nf6 O-O O-O",
+    "c4 c5 Nf3 Nf6 Nc3 d5 cxd5 Nxd5 d4 Nxc3 bxc3",
+    "c4 e6 Nc3 d5 d4 Nf6",
+    "c4 g6 Nc3 Bg7 g3 d6 Bg2 e5",
+    "c4 c6 Nf3 d5 e3 Nf6 Nc3 e6",
+    // Queen's pawn sidelines (A40-A49)
+    "d4 e6 c4 Bb4+ Bd2 Bxd2+ Qxd2",
+    "d4 d6 e4 Nf6 Nc3 g6",
+    "d4 Nf6 Nf3 g6 g3 Bg7 Bg2 O-O O-O d6",
+    "d4 Nf6 Nf3 e6 Bg5 c5 e3 Be7",
+    "d4 Nf6 Bg5 Ne4 Bf4 c5 f3 Qa5+ c3 Nf6",
+    "d4 Nf6 Nf3 b6 g3 Bb7 Bg2 e6 O-O Be7",
+    "d4 Nf6 Nf3 c5 d5 b5",
+    "d4 c5 d5 e5 e4 d6",
+    // Budapest, Old Indian, Benko, Benoni (A50-A79)
+    "d4 Nf6 c4 e5 dxe5 Ng4 Bf4 Nc6 Nf3 Bb4+",
+    "d4 Nf6 c4 d6 Nc3 e5 Nf3 Nbd7 e4 Be7",
+    "d4 Nf6 c4 c5 d5 b5 cxb5 a6 bxa6 Bxa6 Nc3 d6 e4 Bxf1 Kxf1 g6",
+    "d4 Nf6 c4 c5 d5 e6 Nc3 exd5 cxd5 d6 e4 g6 Nf3 Bg7 Be2 O-O O-O",
+    "d4 Nf6 c4 c5 d5 e6 Nc3 exd5 cxd5 d6 Nf3 g6 g3 Bg7 Bg2 O-O O-O",
+    "d4 Nf6 c4 c5 d5 e5 Nc3 d6 e4 Be7",
+    // Dutch (A80-A99)
+    "d4 f5 g3 Nf6 Bg2 g6 Nf3 Bg7 O-O O-O c4 d6 Nc3 Qe8",
+    "d4 f5 c4 Nf6 g3 e6 Bg2 Be7 Nf3 O-O O-O d6 Nc3 Qe8",
+    "d4 f5 c4 Nf6 g3 e6 Bg2 d5 Nf3 c6 O-O Bd6",
+    "d4 f5 Bg5 h6 Bh4 g5 e3 Nf6",
+    "d4 f5 e4 fxe4 Nc3 Nf6 Bg5",
+    // Scandinavian, Nimzowitsch, Alekhine, Pirc, Modern (B00-B09)
+    "e4 d5 exd5 Qxd5 Nc3 Qa5 d4 Nf6 Nf3 c6 Bc4 Bf5",
+    "e4 d5 exd5 Qxd5 Nc3 Qd6 d4 Nf6 Nf3 a6",
+    "e4 d5 exd5 Nf6 d4 Nxd5 Nf3 g6",
+    "e4 Nc6 Nf3 d6 d4 Nf6 Nc3 Bg4",
+    "e4 Nf6 e5 Nd5 d4 d6 Nf3 Bg4 Be2 e6 O-O Be7",
+    "e4 Nf6 e5 Nd5 d4 d6 c4 Nb6 f4 dxe5 fxe5 Nc6",
+    "e4 d6 d4 Nf6 Nc3 g6 f4 Bg7 Nf3 O-O Bd3",
+    "e4 d6 d4 Nf6 Nc3 g6 Nf3 Bg7 Be2 O-O O-O c6",
+    "e4 d6 d4 Nf6 Nc3 g6 Be3 c6 Qd2 b5",
+    "e4 g6 d4 Bg7 Nc3 d6 Be3 a6 Qd2 b5",
+    "e4 g6 d4 Bg7 Nc3 c6 f4 d5 e5 h5",
+    // Caro-Kann (B10-B19)
+    "e4 c6 d4 d5 Nc3 dxe4 Nxe4 Bf5 Ng3 Bg6 h4 h6 Nf3 Nd7 h5 Bh7 Bd3 Bxd3 Qxd3",
+    "e4 c6 d4 d5 Nc3 dxe4 Nxe4 Nd7 Nf3 Ngf6 Nxf6+ Nxf6",
+    "e4 c6 d4 d5 Nd2 dxe4 Nxe4 Bf5 Ng3 Bg6",
+    "e4 c6 d4 d5 e5 Bf5 Nf3 e6 Be2 c5 Be3",
+    "e4 c6 d4 d5 e5 Bf5 Nc3 e6 g4 Bg6 Nge2 c5",
+    "e4 c6 d4 d5 exd5 cxd5 c4 Nf6 Nc3 e6 Nf3 Bb4",
+    "e4 c6 d4 d5 exd5 cxd5 Bd3 Nc6 c3 Nf6 Bf4 Bg4",
+    "e4 c6 Nc3 d5 Nf3 Bg4 h3 Bxf3 Qxf3 e6",
+    // Sicilian (B20-B99)
+    "e4 c5 c3 Nf6 e5 Nd5 d4 cxd4 Nf3 Nc6 cxd4 d6",
+    "e4 c5 c3 d5 exd5 Qxd5 d4 Nf6 Nf3 e6",
+    "e4 c5 Nc3 Nc6 g3 g6 Bg2 Bg7 d3 d6 Be3 e6 Qd2",
+    "e4 c5 Nc3 Nc6 f4 g6 Nf3 Bg7 Bb5 Nd4",
+    "e4 c5 Nf3 Nc6 Bb5 g6 O-O Bg7 Re1 e5",
+    "e4 c5 Nf3 Nc6 Bb5 e6 O-O Nge7 Re1 a6 Bf1 d5",
+    "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 g6 c4 Bg7 Be3 Nf6 Nc3 O-O Be2",
+    "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 e5 Nb5 d6 N1c3 a6 Na3 b5 Nd5",
+    "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 Nf6 Nc3 e5 Ndb5 d6 Bg5 a6 Na3 b5 Bxf6 gxf6 Nd5 f5",
+    "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 Nf6 Nc3 d6 Bg5 e6 Qd2 a6 O-O-O Bd7",
+    "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 Nf6 Nc3 d6 Bc4 e6 Be3 Be7 Qe2 a6 O-O-O Qc7 Bb3",
+    "e4 c5 Nf3 e6 d4 cxd4 Nxd4 Nc6 Nc3 Qc7 Be3 a6 Qf3",
+    "e4 c5 Nf3 e6 d4 cxd4 Nxd4 a6 Bd3 Nf6 O-O Qc7 Qe2 d6",
+    "e4 c5 Nf3 e6 d4 cxd4 Nxd4 Nf6 Nc3 d6 g4 h6 h4 Nc6 Rg1",
+    "e4 c5 Nf3 e6 d4 cxd4 Nxd4 Nf6 Nc3 d6 Be2 Be7 O-O O-O f4 Nc6 Be3",
+    "e4 c5 Nf3 d6 Bb5+ Bd7 Bxd7+ Qxd7 O-O Nc6 c3 Nf6 d4",
+    "e4 c5 Nf3 d6 Bb5+ Nd7 d4 Nf6 Nc3 cxd4 Qxd4 e5 Qd3",
+    "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6 Be3 Bg7 f3 O-O Qd2 Nc6 Bc4 Bd7 O-O-O",
+    "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6 Be2 Bg7 O-O O-O Be3 Nc6 Nb3",
+    "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Be3 e5 Nb3 Be6 f3 Be7 Qd2 O-O O-O-O",
+    "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Be3 e6 f3 b5 Qd2",
+    "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Bg5 e6 f4 Be7 Qf3 Qc7 O-O-O Nbd7",
+    "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Be2 e5 Nb3 Be7 O-O O-O",
+    "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 h3 e5 Nde2 h5",
+    "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Bc4 e6 Bb3 b5 O-O Be7",
+    "e4 c5 Nf3 g6 d4 cxd4 Nxd4 Nc6 c4 Nf6 Nc3 d6",
+    "e4 c5 d4 cxd4 c3 dxc3 Nxc3 Nc6 Nf3 d6 Bc4 e6 O-O Nf6 Qe2 Be7",
+    // French (C00-C19)
+    "e4 e6 d4 d5 Nc3 Nf6 Bg5 Be7 e5 Nfd7 Bxe7 Qxe7 f4 O-O Nf3 c5",
+    "e4 e6 d4 d5 Nc3 Nf6 e5 Nfd7 f4 c5 Nf3 Nc6 Be3",
+    "e4 e6 d4 d5 Nc3 Bb4 e5 c5 a3 Bxc3+ bxc3 Ne7 Qg4 Qc7 Qxg7 Rg8 Qxh7 cxd4 Ne2 Nbc6 f4",
+    "e4 e6 d4 d5 Nc3 Bb4 e5 c5 a3 Bxc3+ bxc3 Ne7 Nf3 b6",
+    "e4 e6 d4 d5 Nc3 dxe4 Nxe4 Nd7 Nf3 Ngf6 Nxf6+ Nxf6",
+    "e4 e6 d4 d5 Nd2 c5 exd5 exd5 Ngf3 Nc6 Bb5 Bd6",
+    "e4 e6 d4 d5 Nd2 Nf6 e5 Nfd7 Bd3 c5 c3 Nc6 Ne2 cxd4 cxd4 f6",
+    "e4 e6 d4 d5 Nd2 c5 exd5 Qxd5 Ngf3 cxd4 Bc4 Qd6 O-O Nf6",
+    "e4 e6 d4 d5 e5 c5 c3 Nc6 Nf3 Qb6 a3 c4",
+    "e4 e6 d4 d5 e5 c5 c3 Nc6 Nf3 Bd7 Be2 Nge7",
+    "e4 e6 d4 d5 exd5 exd5 Nf3 Nf6 Bd3 Bd6 O-O O-O",
+    // Open games (C20-C59)
+    "e4 e5 d4 exd4 Qxd4 Nc6 Qe3 Nf6",
+    "e4 e5 Nc3 Nf6 f4 d5 fxe5 Nxe4 Nf3 Be7",
+    "e4 e5 Nc3 Nc6 g3 Bc5 Bg2 d6",
+    "e4 e5 Bc4 Nf6 d3 c6 Nf3 d5 Bb3",
+    "e4 e5 f4 exf4 Nf3 g5 h4 g4 Ne5 Nf6",
+    "e4 e5 f4 exf4 Nf3 d6 d4 g5 h4 g4 Ng1",
+    "e4 e5 f4 Bc5 Nf3 d6 c3 Nf6 d4 exd4 cxd4 Bb4+",
+    "e4 e5 Nf3 d6 d4 Nf6 Nc3 Nbd7 Bc4 Be7 O-O O-O",
+    "e4 e5 Nf3 d6 d4 exd4 Nxd4 Nf6 Nc3 Be7 Be2 O-O O-O",
+    "e4 e5 Nf3 Nf6 Nxe5 d6 Nf3 Nxe4 d4 d5 Bd3 Nc6 O-O Be7 c4 Nb4 Be2 O-O",
+    "e4 e5 Nf3 Nf6 Nxe5 d6 Nf3 Nxe4 Nc3 Nxc3 dxc3 Be7 Be3 O-O Qd2 Nd7 O-O-O",
+    "e4 e5 Nf3 Nf6 d4 Nxe4 Bd3 d5 Nxe5 Nd7 Nxd7 Bxd7 O-O",
+    "e4 e5 Nf3 Nc6 d4 exd4 Nxd4 Bc5 Be3 Qf6 c3 Nge7 Bc4",
+    "e4 e5 Nf3 Nc6 d4 exd4 Nxd4 Nf6 Nxc6 bxc6 e5 Qe7 Qe2 Nd5 c4",
+    "e4 e5 Nf3 Nc6 d4 exd4 c3 dxc3 Bc4 cxb2 Bxb2",
+    "e4 e5 Nf3 Nc6 Nc3 Nf6 d4 exd4 Nxd4 Bb4 Nxc6 bxc6 Bd3 d5 exd5 cxd5 O-O O-O Bg5 c6",
+    "e4 e5 Nf3 Nc6 Nc3 Nf6 Bb5 Nd4 Ba4 Bc5 Nxe5 O-O",
+    "e4 e5 Nf3 Nc6 Bc4 Bc5 c3 Nf6 d4 exd4 cxd4 Bb4+ Bd2 Bxd2+ Nbxd2 d5",
+    "e4 e5 Nf3 Nc6 Bc4 Bc5 c3 Nf6 d3 d6 O-O O-O Re1 a6 Bb3 Ba7",
+    "e4 e5 Nf3 Nc6 Bc4 Bc5 b4 Bxb4 c3 Ba5 d4 exd4 O-O",
+    "e4 e5 Nf3 Nc6 Bc4 Bc5 O-O Nf6 d3 d6 c3 O-O",
+    "e4 e5 Nf3 Nc6 Bc4 Nf6 Ng5 d5 exd5 Na5 Bb5+ c6 dxc6 bxc6 Be2 h6 Nf3 e4 Ne5",
+    "e4 e5 Nf3 Nc6 Bc4 Nf6 d3 Be7 O-O O-O Re1 d6 c3",
+    "e4 e5 Nf3 Nc6 Bc4 Nf6 d4 exd4 O-O Nxe4 Re1 d5 Bxd5 Qxd5 Nc3",
+    "e4 e5 Nf3 Nc6 Bc4 Be7 d4 d6 d5 Nb8",
+    // Ruy Lopez (C60-C99)
+    "e4 e5 Nf3 Nc6 Bb5 Nf6 O-O Nxe4 d4 Nd6 Bxc6 dxc6 dxe5 Nf5 Qxd8+ Kxd8 Nc3 Ke8 h3 h5",
+    "e4 e5 Nf3 Nc6 Bb5 Nf6 d3 Bc5 c3 O-O O-O d6",
+    "e4 e5 Nf3 Nc6 Bb5 Bc5 c3 Nf6 O-O O-O d4 Bb6",
+    "e4 e5 Nf3 Nc6 Bb5 f5 Nc3 fxe4 Nxe4 d5 Nxe5 dxe4 Nxc6 Qg5",
+    "e4 e5 Nf3 Nc6 Bb5 g6 c3 a6 Ba4 d6 d4 Bd7",
+    "e4 e5 Nf3 Nc6 Bb5 d6 d4 Bd7 Nc3 Nf6 O-O Be7 Re1 exd4 Nxd4",
+    "e4 e5 Nf3 Nc6 Bb5 a6 Bxc6 dxc6 O-O f6 d4 exd4 Nxd4 c5 Nb3 Qxd1 Rxd1",
+    "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 d6 c3 Bd7 d4 Nge7",
+    "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 d3 b5 Bb3 Be7 O-O d6 c3 O-O",
+    "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 Qe2 b5 Bb3 Be7 c3 O-O O-O d5",
+    "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Nxe4 d4 b5 Bb3 d5 dxe5 Be6 c3 Bc5 Nbd2 O-O Bc2",
+    "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O b5 Bb3 Bb7 d3 Be7 Nc3 O-O",
+    "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O b5 Bb3 Bc5 c3 d6 d4 Bb6",
+    "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Bxc6 dxc6 d3 Nd7 Nbd2 O-O",
+    "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 O-O c3 d5 exd5 Nxd5 Nxe5 Nxe5 Rxe5 c6 d4 Bd6 Re1 Qh4 g3 Qh3",
+    "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 O-O h3 Bb7 d3 d6 a3",
+    "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 O-O a4 b4 d3 d6",
+    "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 d6 c3 O-O h3 Nb8 d4 Nbd7 Nbd2 Bb7 Bc2 Re8 Nf1 Bf8 Ng3",
+    "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 d6 c3 O-O h3 Na5 Bc2 c5 d4 Qc7 Nbd2 cxd4 cxd4",
+    "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 d6 c3 O-O h3 Bb7 d4 Re8 Nbd2 Bf8 a4 h6 Bc2 exd4 cxd4 Nb4 Bb1 c5 d5 Nd7 Ra3",
+    "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 d6 c3 O-O h3 Nd7 d4 Bf6 a4 Bb7",
+    "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 d6 c3 O-O h3 h6 d4 Re8 Nbd2 Bf8",
+    "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 d6 c3 O-O d4 Bg4 d5 Na5 Bc2 c6",
+    "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 d6 c3 O-O d4 Bd7",
+    "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 d3 b5 Bb3 d6 a4 Bd7",
+    // Queen's Gambit and closed games (D00-D69)
+    "d4 d5 Bf4 Nf6 e3 c5 c3 Nc6 Nd2 e6 Ngf3 Bd6 Bg3 O-O Bd3",
+    "d4 d5 Nf3 Nf6 Bf4 c5 e3 Nc6 c3 Qb6 Qb3",
+    "d4 d5 Nf3 Nf6 e3 e6 Bd3 c5 b3 Nc6 O-O Bd6 Bb2 O-O",
+    "d4 d5 Bg5 h6 Bh4 c6 Nf3 Qb6 Qc1",
+    "d4 d5 Nc3 Nf6 Bg5 Nbd7 Nf3 g6 e3 Bg7",
+    "d4 d5 c4 Nc6 Nc3 dxc4 Nf3 Nf6 e4 Bg4",
+    "d4 d5 c4 e5 dxe5 d4 Nf3 Nc6 g3 Be6 Nbd2",
+    "d4 d5 c4 dxc4 Nf3 Nf6 e3 e6 Bxc4 c5 O-O a6 Qe2 b5 Bb3 Bb7 Nc3 Nbd7 Rd1",
+    "d4 d5 c4 dxc4 e4 e5 Nf3 exd4 Bxc4 Nc6 O-O Be6",
+    "d4 d5 c4 c6 Nf3 Nf6 Nc3 dxc4 a4 Bf5 e3 e6 Bxc4 Bb4 O-O O-O Qe2 Nbd7",
+    "d4 d5 c4 c6 Nf3 Nf6 Nc3 e6 Bg5 dxc4 e4 b5 e5 h6 Bh4 g5 Nxg5 hxg5 Bxg5 Nbd7",
+    "d4 d5 c4 c6 Nf3 Nf6 Nc3 e6 e3 Nbd7 Bd3 dxc4 Bxc4 b5 Bd3 Bb7 O-O a6 e4 c5 d5",
+    "d4 d5 c4 c6 Nf3 Nf6 Nc3 e6 e3 Nbd7 Qc2 Bd6 Bd3 O-O O-O dxc4 Bxc4",
+    "d4 d5 c4 c6 Nf3 Nf6 Nc3 a6 c5 Nbd7 Bf4 Nh5 Bd2",
+    "d4 d5 c4 c6 Nf3 Nf6 e3 Bf5 Nc3 e6 Nh4 Bg6 Nxg6 hxg6",
+    "d4 d5 c4 c6 cxd5 cxd5 Nc3 Nf6 Bf4 Nc6 e3 Bf5 Nf3 e6 Qb3 Bb4",
+    "d4 d5 c4 c6 Nc3 e6 e4 dxe4 Nxe4 Bb4+ Bd2 Qxd4 Bxb4 Qxe4+ Be2",
+    "d4 d5 c4 e6 Nc3 c5 cxd5 exd5 Nf3 Nc6 g3 Nf6 Bg2 Be7 O-O O-O",
+    "d4 d5 c4 e6 Nc3 Nf6 cxd5 exd5 Bg5 Be7 e3 c6 Qc2 Nbd7 Bd3 O-O Nge2 Re8 O-O",
+    "d4 d5 c4 e6 Nc3 Nf6 Bg5 Be7 e3 O-O Nf3 h6 Bh4 b6 Be2 Bb7 Bxf6 Bxf6 cxd5 exd5 b4",
+    "d4 d5 c4 e6 Nc3 Nf6 Bg5 Be7 e3 O-O Nf3 h6 Bh4 Ne4 Bxe7 Qxe7 cxd5 Nxc3 bxc3 exd5",
+    "d4 d5 c4 e6 Nc3 Nf6 Bg5 Be7 e3 O-O Nf3 Nbd7 Rc1 c6 Bd3 dxc4 Bxc4 Nd5 Bxe7 Qxe7 O-O Nxc3 Rxc3 e5",
+    "d4 d5 c4 e6 Nc3 Nf6 Bg5 Nbd7 e3 c6 Nf3 Qa5 Nd2 Bb4 Qc2 O-O",
+    "d4 d5 c4 e6 Nc3 Nf6 Nf3 Be7 Bf4 O-O e3 c5 dxc5 Bxc5 Qc2 Nc6 a3 Qa5",
+    "d4 d5 c4 e6 Nc3 Nf6 Nf3 Bb4 Bg5 Nbd7 cxd5 exd5 Qc2 c5",
+    "d4 d5 c4 e6 Nc3 Nf6 Nf3 dxc4 e4 Bb4 Bg5 c5 Bxc4 cxd4 Nxd4 Bxc3+ bxc3 Qa5",
+    "d4 d5 c4 e6 Nc3 Be7 Nf3 Nf6 Bf4 O-O e3 Nbd7 c5 c6",
+    "d4 d5 c4 e6 Nf3 Nf6 g3 dxc4 Bg2 a6 O-O b5",
+    "d4 d5 c4 e6 Nf3 Nf6 g3 Be7 Bg2 O-O O-O dxc4 Qc2 a6 Qxc4 b5 Qc2 Bb7 Bd2",
+    // Grünfeld (D70-D99)
+    "d4 Nf6 c4 g6 Nc3 d5 cxd5 Nxd5 e4 Nxc3 bxc3 Bg7 Nf3 c5 Rb1 O-O Be2 cxd4 cxd4 Qa5+ Bd2 Qxa2 O-O",
+    "d4 Nf6 c4 g6 Nc3 d5 cxd5 Nxd5 e4 Nxc3 bxc3 Bg7 Bc4 c5 Ne2 Nc6 Be3 O-O O-O",
+    "d4 Nf6 c4 g6 Nc3 d5 Nf3 Bg7 Qb3 dxc4 Qxc4 O-O e4 Bg4 Be3 Nfd7",
+    "d4 Nf6 c4 g6 Nc3 d5 Bf4 Bg7 e3 c5 dxc5 Qa5 Rc1 dxc4 Bxc4 O-O Nf3",
+    "d4 Nf6 c4 g6 Nc3 d5 Nf3 Bg7 Bg5 Ne4 Bh4 Nxc3 bxc3 dxc4 e3",
+    "d4 Nf6 c4 g6 g3 c6 Bg2 d5 Nf3 Bg7 O-O O-O",
+    "d4 Nf6 c4 g6 g3 Bg7 Bg2 d5 cxd5 Nxd5 Nf3 O-O O-O",
+    // Queen's Indian, Catalan, Bogo (E00-E19)
+    "d4 Nf6 c4 e6 g3 d5 Bg2 Be7 Nf3 O-O O-O dxc4 Qc2 a6 Qxc4 b5 Qc2 Bb7",
+    "d4 Nf6 c4 e6 g3 d5 Bg2 dxc4 Nf3 c5 O-O Nc6 Qa4 Bd7 Qxc4 b5 Qd3",
+    "d4 Nf6 c4 e6 Nf3 Bb4+ Bd2 Qe7 g3 Nc6 Nc3 Bxc3 Bxc3 Ne4",
+    "d4 Nf6 c4 e6 Nf3 Bb4+ Bd2 a5 g3 d5 Qc2",
+    "d4 Nf6 c4 e6 Nf3 b6 g3 Ba6 b3 Bb4+ Bd2 Be7 Bg2 c6 Bc3 d5 Ne5 Nfd7 Nxd7 Nxd7 Nd2 O-O O-O",
+    "d4 Nf6 c4 e6 Nf3 b6 g3 Bb7 Bg2 Be7 O-O O-O Nc3 Ne4 Qc2 Nxc3 Qxc3",
+    "d4 Nf6 c4 e6 Nf3 b6 a3 Bb7 Nc3 d5 cxd5 Nxd5 Qc2 Nxc3 bxc3 Be7 e4 O-O Bd3 c5",
+    "d4 Nf6 c4 e6 Nf3 b6 e3 Bb7 Bd3 d5 O-O Bd6 b3 O-O Bb2",
+    "d4 Nf6 c4 e6 Nf3 d5 Nc3 c6 Bg5 h6 Bxf6 Qxf6 e3 Nd7 Bd3 dxc4 Bxc4 g6 O-O Bg7",
+    // Nimzo-Indian (E20-E59)
+    "d4 Nf6 c4 e6 Nc3 Bb4 Qc2 O-O a3 Bxc3+ Qxc3 b6 Bg5 Bb7 f3 h6 Bh4 d5 e3 Nbd7",
+    "d4 Nf6 c4 e6 Nc3 Bb4 Qc2 d5 cxd5 exd5 Bg5 h6 Bh4 c5 dxc5 g5 Bg3 Ne4 e3 Qa5",
+    "d4 Nf6 c4 e6 Nc3 Bb4 Qc2 c5 dxc5 O-O a3 Bxc5 Nf3 b6 Bf4",
+    "d4 Nf6 c4 e6 Nc3 Bb4 e3 O-O Bd3 d5 Nf3 c5 O-O Nc6 a3 Bxc3 bxc3 dxc4 Bxc4 Qc7",
+    "d4 Nf6 c4 e6 Nc3 Bb4 e3 O-O Bd3 c5 Nf3 d5 O-O dxc4 Bxc4 Nbd7 Qe2 b6",
+    "d4 Nf6 c4 e6 Nc3 Bb4 e3 b6 Nge2 Ba6 Ng3 Bxc3+ bxc3 d5",
+    "d4 Nf6 c4 e6 Nc3 Bb4 e3 c5 Bd3 Nc6 Nf3 Bxc3+ bxc3 d6 e4 e5 d5 Ne7",
+    "d4 Nf6 c4 e6 Nc3 Bb4 f3 d5 a3 Bxc3+ bxc3 c5 cxd5 Nxd5 dxc5 f5",
+    "d4 Nf6 c4 e6 Nc3 Bb4 Bg5 h6 Bh4 c5 d5 d6 e3 exd5 cxd5 Nbd7",
+    "d4 Nf6 c4 e6 Nc3 Bb4 Nf3 c5 g3 cxd4 Nxd4 O-O Bg2 d5 cxd5 Nxd5 Qb3 Qa5 Bd2 Nc6 Nxc6 bxc6 O-O",
+    "d4 Nf6 c4 e6 Nc3 Bb4 Qb3 c5 dxc5 Nc6 Nf3 Ne4 Bd2 Nxd2 Nxd2 f5",
+    // King's Indian (E60-E99)
+    "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3 O-O Be2 e5 O-O Nc6 d5 Ne7 Ne1 Nd7 Nd3 f5 Bd2 Nf6 f3 f4",
+    "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3 O-O Be2 e5 O-O Nc6 d5 Ne7 b4 Nh5 Re1 f5 Ng5 Nf6 Bf3 c6",
+    "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3 O-O Be2 e5 O-O Na6 Be3 Ng4 Bg5 Qe8 dxe5 dxe5 h3 h6 Bd2 Nf6 Be3",
+    "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3 O-O Be2 e5 O-O Nbd7 Be3 c6 d5 c5",
+    "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3 O-O Be2 e5 d5 a5 Bg5 h6 Bh4 Na6 Nd2 Qe8 O-O Nh7",
+    "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3 O-O Be2 e5 dxe5 dxe5 Qxd8 Rxd8 Bg5 Re8",
+    "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 f3 O-O Be3 e5 d5 c6 Qd2 cxd5 cxd5 a6 Bd3 Nbd7 Nge2",
+    "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 f3 O-O Be3 c5 Nge2 Nc6 d5 Ne5 Ng3 e6",
+    "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 f3 O-O Be3 Nc6 Nge2 a6 Qd2 Rb8 h4 h5 Bh6",
+    "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Be2 O-O Bg5 c5 d5 e6 Qd2 exd5 exd5 Re8 Nf3 Bg4",
+    "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 f4 O-O Nf3 c5 d5 e6 Be2 exd5 cxd5 Bg4 O-O Nbd7",
+    "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 h3 O-O Be3 e5 d5 a5 g4 Na6",
+    "d4 Nf6 c4 g6 Nc3 Bg7 Nf3 O-O g3 d6 Bg2 Nbd7 O-O e5 e4 c6 h3 Qb6",
+    "d4 Nf6 c4 g6 Nc3 Bg7 Nf3 O-O g3 d6 Bg2 Nc6 O-O a6 d5 Na5 Nd2 c5 Qc2 Rb8 b3 b5",
+    "d4 Nf6 c4 g6 Nc3 Bg7 Nf3 O-O Bf4 d6 e3 Nbd7 h3 c5",
+    "d4 Nf6 c4 g6 Nc3 Bg7 Nf3 O-O Bg5 d6 e3 c5 Be2",
+];
+
+/// Errors raised while building an opening book.
+#[derive(Error, Debug)]
+pub enum OpeningBookError {
+    #[error("Failed to parse SAN move {san} in opening line {line}")]
+    SanParseError { line: usize, san: String },
+    #[error("Illegal move {san} in opening line {line}: {error}")]
+    IllegalMove {
+        line: usize,
+        san: String,
+        error: SanError,
+    },
+}
+
+// A node of the trie. Visible to the crate for the book `build.rs` generates.
+#[derive(Debug, Clone)]
+pub(crate) struct Node {
+    pub(crate) parent: u32,
+    pub(crate) mv: Option<Move>,
+    pub(crate) depth: u16,
+    pub(crate) children: Cow<'static, [(Move, u32)]>,
+}
+
+/// A trie of opening lines from the starting position.
+///
+/// Node 0 is the root (no moves played). Every other node is reached by a
+/// unique sequence of moves and is identified by its index, which a compressed
+/// game stores in place of the moves leading to it.
+#[derive(Debug, Clone)]
+pub struct OpeningBook {
+    nodes: Cow<'static, [Node]>,
+}
+
+impl OpeningBook {
+    /// Wraps the nodes of a built book, so that a static book needs no
+    /// initialization.
+    pub(crate) const fn from_static(nodes: &'static [Node]) -> Self {
+        OpeningBook {
+            nodes: Cow::Borrowed(nodes),
+        }
+    }
+
+    /// Returns the nodes, for `build.rs` to write out.
+    #[allow(dead_code)] // Used by `build.rs`
+    pub(crate) fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// Builds a book from lines of space-separated SAN moves.
+    ///
+    /// Nodes are numbered in the order they are first reached, so the same
+    /// lines in the same order always produce the same node IDs.
+    pub fn from_lines<'a, I>(lines: I) -> Result<Self, OpeningBookError>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut book = OpeningBook {
+            nodes: Cow::Owned(vec![Node {
+                parent: 0,
+                mv: None,
+                depth: 0,
+                children: Cow::Borrowed(&[]),
+            }]),
+        };
+
+        for (line_index, line) in lines.into_iter().enumerate() {
+            let mut board = Chess::default();
+            let mut node = 0;
+            for san_str in line.split_whitespace() {
+                let san: San = san_str
+                    .parse()
+                    .map_err(|_| OpeningBookError::SanParseError {
+                        line: line_index,
+                        san: san_str.to_string(),
+                    })?;
+                let mv = san
+                    .to_move(&board)
+                    .map_err(|error| OpeningBookError::IllegalMove {
+                        line: line_index,
+                        san: san_str.to_string(),
+                        error,
+                    })?;
+                node = book.child_or_insert(node, &mv);
+                board.play_unchecked(&mv);
+            }
+        }
+
+        Ok(book)
+    }
+
+    /// Builds a book from the opening moves of a corpus of games.
+    ///
+    /// Every prefix of at most `max_plies` moves shared by at least
+    /// `min_games` games becomes a node. Games are plain SAN move lists.
+    pub fn from_games<G, S>(
+        games: &[G],
+        max_plies: usize,
+        min_games: usize,
+    ) -> Result<Self, OpeningBookError>
+    where
+        G: AsRef<[S]>,
+        S: AsRef<str>,
+    {
+        let mut lines: Vec<String> = Vec::new();
+        let mut counts: BTreeMap<String, usize> = Default::default();
+        for game in games {
+            let mut prefix = String::new();
+            for san in game.as_ref().iter().take(max_plies) {
+                if !prefix.is_empty() {
+                    prefix.push(' ');
+                }
+                prefix.push_str(san.as_ref());
+                let count = counts.entry(prefix.clone()).or_insert(0);
+                *count += 1;
+                if *count == min_games {
+                    lines.push(prefix.clone());
+                }
+            }
+        }
+        Self::from_lines(lines.iter().map(String::as_str))
+    }
+
+    fn child_or_insert(&mut self, node: u32, mv: &Move) -> u32 {
+        if let Some(child) = self.child(node, mv) {
+            return child;
+        }
+        let nodes = self.nodes.to_mut();
+        let child = nodes.len() as u32;
+        let depth = nodes[node as usize].depth + 1;
+        nodes.push(Node {
+            parent: node,
+            mv: Some(mv.clone()),
+            depth,
+            children: Cow::Borrowed(&[]),
+        });
+        nodes[node as usize]
+            .children
+            .to_mut()
+            .push((mv.clone(), child));
+        child
+    }
+
+    /// Returns the node reached by playing `mv` from `node`, if it is in the book.
+    pub fn child(&self, node: u32, mv: &Move) -> Option<u32> {
+        self.nodes
+            .get(node as usize)?
+            .children
+            .iter()
+            .find(|(child_mv, _)| child_mv == mv)
+            .map(|&(_, child)| child)
+    }
+
+    /// Returns the number of nodes, including the root.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns whether the book only contains the root.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len() <= 1
+    }
+
+    /// Returns the number of moves leading to `node`.
+    pub fn depth(&self, node: u32) -> Option<usize> {
+        self.nodes.get(node as usize).map(|n| n.depth as usize)
+    }
+
+    /// Returns the moves leading from the starting position to `node`.
+    pub fn line(&self, node: u32) -> Option<Vec<Move>> {
+        let mut node = self.nodes.get(node as usize)?;
+        let mut moves = Vec::with_capacity(node.depth as usize);
+        while let Some(mv) = &node.mv {
+            moves.push(mv.clone());
+            node = &self.nodes[node.parent as usize];
+        }
+        moves.reverse();
+        Some(moves)
+    }
+
+    /// Number of bits used to store a node ID.
+    pub fn id_bits(&self) -> u32 {
+        usize::BITS - (self.nodes.len() - 1).leading_zeros()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eco_lines_are_legal() {
+        if let Err(e) = OpeningBook::from_lines(ECO_LINES.iter().copied()) {
+            panic!("{}", e);
+        }
+    }
+
+    #[test]
+    fn test_eco_book_is_frozen() {
+        // Node IDs are part of the V3 format; changing ECO_LINES breaks stored games
+        let book = crate::eco_book::eco_book();
+        assert_eq!(book.len(), 1951);
+        assert_eq!(book.id_bits(), 11);
+    }
+
+    #[test]
+    fn test_line_roundtrip() {
+        let book = OpeningBook::from_lines(["e4 e5 Nf3", "e4 c5", "d4"]).unwrap();
+        assert_eq!(book.len(), 6);
+
+        let mut board = Chess::default();
+        let mut node = 0;
+        for san in ["e4", "e5", "Nf3"] {
+            let mv = san.parse::<San>().unwrap().to_move(&board).unwrap();
+            node = book.child(node, &mv).unwrap();
+            board.play_unchecked(&mv);
+        }
+        assert_eq!(node, 3);
+        assert_eq!(book.depth(node), Some(3));
+        assert_eq!(book.line(node).unwrap().len(), 3);
+        assert_eq!(book.id_bits(), 3);
+    }
+
+    #[test]
+    fn test_from_games_keeps_common_prefixes() {
+        let games = [
+            vec!["e4", "e5", "Nf3"],
+            vec!["e4", "e5", "Bc4"],
+            vec!["d4", "d5"],
+        ];
+        let book = OpeningBook::from_games(&games, 8, 2).unwrap();
+        // Root, e4 and e4 e5
+        assert_eq!(book.len(), 3);
+    }
+
+    #[test]
+    fn test_corpus_book_codec_roundtrip() {
+        use crate::{CodecVersion, Decoder, Encoder};
+
+        let games = [
+            vec!["d4", "Nf6", "c4", "e6", "Nc3", "Bb4", "e3", "O-O"],
+            vec!["d4", "Nf6", "c4", "e6", "Nc3", "Bb4", "Qc2", "d5"],
+            vec!["e4", "c5", "Nf3", "d6"],
+        ];
+        let book = OpeningBook::from_games(&games, 8, 2).unwrap();
+        // Root and the six shared moves of the Nimzo-Indian
+        assert_eq!(book.len(), 7);
+
+        let game = &games[0];
+        let mut with_book = Encoder::with_book(CodecVersion::V2, &book);
+        let mut without_book = Encoder::with_version(CodecVersion::V2);
+        for mv in game {
+            with_book.encode_move(mv).unwrap();
+            without_book.encode_move(mv).unwrap();
+        }
+        let compressed = with_book.finalize();
+        assert!(compressed.len() < without_book.finalize().len());
+
+        let decoded = Decoder::with_book(CodecVersion::V2, &book)
+            .decode(&compressed, game.len())
+            .unwrap();
+        assert_eq!(decoded, *game);
+
+        // A game stopped inside the book resumes into the same bits
+        let mut head = Encoder::with_book(CodecVersion::V2, &book);
+        for mv in &game[..5] {
+            head.encode_move(mv).unwrap();
+        }
+        let stored = head.finalize().to_bytes();
+        let mut resumed = Encoder::resume_with_book(&stored, 5, CodecVersion::V2, &book).unwrap();
+        for mv in &game[5..] {
+            resumed.encode_move(mv).unwrap();
+        }
+        assert_eq!(resumed.finalize(), compressed);
+    }
+}
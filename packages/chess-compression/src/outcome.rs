@@ -0,0 +1,256 @@
+// src/outcome.rs
+use bit_vec::BitVec;
+use core::fmt;
+use core::str::FromStr;
+
+/// Result of a game, as written at the end of PGN movetext.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum GameResult {
+    #[default]
+    Unknown,
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+impl GameResult {
+    /// Returns the PGN result token (`1-0`, `0-1`, `1/2-1/2` or `*`).
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameResult::Unknown => "*",
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+        }
+    }
+
+    fn to_bits(self) -> u8 {
+        match self {
+            GameResult::Unknown => 0,
+            GameResult::WhiteWins => 1,
+            GameResult::BlackWins => 2,
+            GameResult::Draw => 3,
+        }
+    }
+
+    fn from_bits(bits: u8) -> Option<Self> {
+        match bits {
+            0 => Some(GameResult::Unknown),
+            1 => Some(GameResult::WhiteWins),
+            2 => Some(GameResult::BlackWins),
+            3 => Some(GameResult::Draw),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for GameResult {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "*" => Ok(GameResult::Unknown),
+            "1-0" => Ok(GameResult::WhiteWins),
+            "0-1" => Ok(GameResult::BlackWins),
+            "1/2-1/2" => Ok(GameResult::Draw),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Why a game ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Termination {
+    #[default]
+    Unknown,
+    /// Ended over the board without further detail, as in lichess' `Normal`.
+    Normal,
+    Checkmate,
+    Resignation,
+    TimeForfeit,
+    Adjudication,
+    Abandoned,
+    Stalemate,
+    Agreement,
+    Repetition,
+    FiftyMoveRule,
+    InsufficientMaterial,
+    RulesInfraction,
+}
+
+impl Termination {
+    const ALL: [Termination; 13] = [
+        Termination::Unknown,
+        Termination::Normal,
+        Termination::Checkmate,
+        Termination::Resignation,
+        Termination::TimeForfeit,
+        Termination::Adjudication,
+        Termination::Abandoned,
+        Termination::Stalemate,
+        Termination::Agreement,
+        Termination::Repetition,
+        Termination::FiftyMoveRule,
+        Termination::InsufficientMaterial,
+        Termination::RulesInfraction,
+    ];
+
+    /// Parses the value of a PGN `Termination` tag, case-insensitively.
+    pub fn from_pgn_tag(value: &str) -> Self {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "normal" => Termination::Normal,
+            "checkmate" | "mate" => Termination::Checkmate,
+            "resignation" | "resigned" | "resign" => Termination::Resignation,
+            "time forfeit" | "time" | "timeout" => Termination::TimeForfeit,
+            "adjudication" | "adjudicated" => Termination::Adjudication,
+            "abandoned" => Termination::Abandoned,
+            "stalemate" => Termination::Stalemate,
+            "agreement" | "draw agreed" => Termination::Agreement,
+            "repetition" | "threefold repetition" => Termination::Repetition,
+            "fifty move rule" | "50 move rule" => Termination::FiftyMoveRule,
+            "insufficient material" => Termination::InsufficientMaterial,
+            "rules infraction" => Termination::RulesInfraction,
+            _ => Termination::Unknown,
+        }
+    }
+
+    /// Returns the value to write in a PGN `Termination` tag.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Termination::Unknown => "Unknown",
+            Termination::Normal => "Normal",
+            Termination::Checkmate => "Checkmate",
+            Termination::Resignation => "Resignation",
+            Termination::TimeForfeit => "Time forfeit",
+            Termination::Adjudication => "Adjudication",
+            Termination::Abandoned => "Abandoned",
+            Termination::Stalemate => "Stalemate",
+            Termination::Agreement => "Agreement",
+            Termination::Repetition => "Repetition",
+            Termination::FiftyMoveRule => "Fifty move rule",
+            Termination::InsufficientMaterial => "Insufficient material",
+            Termination::RulesInfraction => "Rules infraction",
+        }
+    }
+
+    fn to_bits(self) -> u8 {
+        Self::ALL.iter().position(|&t| t == self).unwrap_or(0) as u8
+    }
+
+    fn from_bits(bits: u8) -> Option<Self> {
+        Self::ALL.get(bits as usize).copied()
+    }
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Result and termination reason stored after the moves of a compressed game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct GameOutcome {
+    pub result: GameResult,
+    pub termination: Termination,
+}
+
+/// Size of the outcome trailer: a set marker bit, 2 result bits, 4 termination
+/// bits and a reserved zero bit.
+pub(crate) const TRAILER_BITS: usize = 8;
+
+impl GameOutcome {
+    pub fn new(result: GameResult, termination: Termination) -> Self {
+        GameOutcome {
+            result,
+            termination,
+        }
+    }
+
+    pub(crate) fn write_trailer(&self, buffer: &mut BitVec) {
+        buffer.push(true);
+        push_bits(buffer, self.result.to_bits(), 2);
+        push_bits(buffer, self.termination.to_bits(), 4);
+        buffer.push(false);
+    }
+
+    /// Reads a trailer from the bits left after the last move.
+    ///
+    /// Games without a trailer leave at most 7 zero bits of byte padding, so
+    /// anything that is not a well-formed trailer followed by padding yields
+    /// `None`.
+    pub(crate) fn read_trailer<I>(mut bits: I) -> Option<Self>
+    where
+        I: ExactSizeIterator<Item = bool>,
+    {
+        if !(TRAILER_BITS..TRAILER_BITS + 8).contains(&bits.len()) || !bits.next()? {
+            return None;
+        }
+        let result = GameResult::from_bits(read_bits(&mut bits, 2))?;
+        let termination = Termination::from_bits(read_bits(&mut bits, 4))?;
+        if bits.any(|bit| bit) {
+            return None;
+        }
+        Some(GameOutcome::new(result, termination))
+    }
+}
+
+fn push_bits(buffer: &mut BitVec, value: u8, count: u32) {
+    for i in (0..count).rev() {
+        buffer.push((value >> i) & 1 == 1);
+    }
+}
+
+fn read_bits(bits: &mut impl Iterator<Item = bool>, count: u32) -> u8 {
+    (0..count).fold(0, |acc, _| {
+        (acc << 1) | u8::from(bits.next().unwrap_or(false))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trailer_roundtrip() {
+        for termination in Termination::ALL {
+            for result in [
+                GameResult::Unknown,
+                GameResult::WhiteWins,
+                GameResult::BlackWins,
+                GameResult::Draw,
+            ] {
+                let outcome = GameOutcome::new(result, termination);
+                let mut buffer = BitVec::new();
+                outcome.write_trailer(&mut buffer);
+                assert_eq!(buffer.len(), TRAILER_BITS);
+                assert_eq!(GameOutcome::read_trailer(buffer.iter()), Some(outcome));
+            }
+        }
+    }
+
+    #[test]
+    fn test_padding_is_not_a_trailer() {
+        let padding = BitVec::from_elem(7, false);
+        assert_eq!(GameOutcome::read_trailer(padding.iter()), None);
+    }
+
+    #[test]
+    fn test_termination_tags() {
+        assert_eq!(
+            Termination::from_pgn_tag("Time forfeit"),
+            Termination::TimeForfeit
+        );
+        assert_eq!(
+            Termination::from_pgn_tag("abandoned"),
+            Termination::Abandoned
+        );
+        assert_eq!(Termination::from_pgn_tag("whatever"), Termination::Unknown);
+    }
+}
@@ -0,0 +1,684 @@
+// src/pgn_document.rs
+use crate::decoder::Decoder;
+use crate::outcome::GameResult;
+use crate::pgn_compress::{san_to_move, CodecVersion, Encoder, EncoderError};
+use alloc::collections::BTreeMap;
+use alloc::{format, string::String, string::ToString, vec::Vec};
+use bit_vec::BitVec;
+use core::fmt;
+use shakmaty::{san::SanPlus, Chess};
+use thiserror::Error;
+
+/// A PGN game: its tag pairs in order, mainline moves and result token.
+///
+/// Comments, NAGs and variations are not part of the model. Moves are stored
+/// by their rank, not their text, so `CompressedPgn` only accepts them in
+/// canonical SAN: minimal disambiguation and a `+` or `#` suffix exactly when
+/// the move checks or mates, as in `Nf3` and `Qxf7#`. Use `normalize_moves`
+/// on games read from elsewhere.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<String>,
+    /// The token ending the movetext, if any.
+    pub result: Option<GameResult>,
+}
+
+impl PgnGame {
+    /// Returns the value of the first tag with the given name.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Rewrites the moves in canonical SAN, such as `Ngf3` as `Nf3` when no
+    /// other knight can reach f3.
+    pub fn normalize_moves(&mut self) -> Result<(), EncoderError> {
+        let mut position = Chess::default();
+        for (ply, san) in self.moves.iter_mut().enumerate() {
+            let mv = san_to_move(&position, ply, san)?;
+            *san = SanPlus::from_move_and_play_unchecked(&mut position, &mv).to_string();
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for PgnGame {
+    /// Writes the game as PGN, with movetext lines of at most 80 characters.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.tags {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            writeln!(f, "[{} \"{}\"]", name, value)?;
+        }
+        if !self.tags.is_empty() {
+            writeln!(f)?;
+        }
+
+        let mut tokens = Vec::with_capacity(self.moves.len() * 3 / 2 + 1);
+        for (ply, mv) in self.moves.iter().enumerate() {
+            if ply % 2 == 0 {
+                tokens.push(format!("{}.", ply / 2 + 1));
+            }
+            tokens.push(mv.clone());
+        }
+        if let Some(result) = self.result {
+            tokens.push(result.to_string());
+        }
+
+        let mut line_len = 0;
+        for token in tokens {
+            if line_len > 0 && line_len + 1 + token.len() > 80 {
+                writeln!(f)?;
+                line_len = 0;
+            }
+            if line_len > 0 {
+                f.write_str(" ")?;
+                line_len += 1;
+            }
+            f.write_str(&token)?;
+            line_len += token.len();
+        }
+        writeln!(f)
+    }
+}
+
+/// Errors raised while compressing or decompressing a PGN document.
+#[derive(Error, Debug)]
+pub enum CompressedPgnError {
+    #[error(transparent)]
+    Moves(#[from] EncoderError),
+    #[error("Compressed PGN document ends unexpectedly")]
+    Truncated,
+    #[error("Unknown codec version {0} in compressed PGN document")]
+    UnknownVersion(u8),
+    #[error("Codec version {0:?} is not frozen and cannot be stored")]
+    UnfrozenVersion(CodecVersion),
+    #[error("Unknown tag name ID {0} in compressed PGN document")]
+    UnknownTag(u8),
+    #[error("String reference {0} is not in the string table")]
+    UnknownString(u32),
+    #[error("Invalid UTF-8 in compressed PGN document")]
+    InvalidUtf8,
+    #[error("Invalid result token in compressed PGN document")]
+    InvalidResult,
+    #[error("Move {san} at ply {ply} is not in canonical SAN, which is {canonical}")]
+    NonCanonicalSan {
+        ply: usize,
+        san: String,
+        canonical: String,
+    },
+}
+
+/// Tag names coded as a single byte. ID 0 is followed by the name itself.
+const KNOWN_TAGS: [&str; 30] = [
+    "Event",
+    "Site",
+    "Date",
+    "Round",
+    "White",
+    "Black",
+    "Result",
+    "WhiteElo",
+    "BlackElo",
+    "ECO",
+    "Opening",
+    "Variation",
+    "TimeControl",
+    "Termination",
+    "UTCDate",
+    "UTCTime",
+    "EventDate",
+    "WhiteTitle",
+    "BlackTitle",
+    "WhiteFideId",
+    "BlackFideId",
+    "WhiteRatingDiff",
+    "BlackRatingDiff",
+    "WhiteTeam",
+    "BlackTeam",
+    "Annotator",
+    "PlyCount",
+    "Mode",
+    "Board",
+    "EventType",
+];
+
+#[derive(Clone, Copy)]
+enum FieldCoder {
+    Date,
+    Number,
+    TimeControl,
+    /// Values that repeat across games, such as player and event names.
+    Table,
+    Raw,
+}
+
+fn field_coder(name: &str) -> FieldCoder {
+    match name {
+        "Date" | "UTCDate" | "EventDate" => FieldCoder::Date,
+        "WhiteElo" | "BlackElo" | "WhiteFideId" | "BlackFideId" | "PlyCount" | "Board" => {
+            FieldCoder::Number
+        }
+        "TimeControl" => FieldCoder::TimeControl,
+        "Event" | "Site" | "White" | "Black" | "WhiteTeam" | "BlackTeam" | "Annotator"
+        | "Opening" | "Variation" => FieldCoder::Table,
+        _ => FieldCoder::Raw,
+    }
+}
+
+/// Compresses whole PGN games: tags, moves and result.
+///
+/// Tag names from a fixed dictionary take one byte, dates, ratings and time
+/// controls are stored as numbers when they are in their usual format, and
+/// names such as players and events go through a string table. Each document
+/// starts with its codec version, so it can be decoded without out-of-band
+/// information.
+///
+/// The string table grows with every game, so a sequence of documents must
+/// be decompressed in the order it was compressed, by a single instance. Use a
+/// fresh instance per game for self-contained documents.
+#[derive(Debug, Clone, Default)]
+pub struct CompressedPgn {
+    version: CodecVersion,
+    strings: Vec<String>,
+    string_ids: BTreeMap<String, u32>,
+}
+
+impl CompressedPgn {
+    /// Creates a codec that compresses moves with the default codec version.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a codec that compresses moves with the given codec version.
+    ///
+    /// Fails with `UnfrozenVersion` for a version that is not frozen, since
+    /// documents are meant to be stored.
+    pub fn with_version(version: CodecVersion) -> Result<Self, CompressedPgnError> {
+        if !version.is_frozen() {
+            return Err(CompressedPgnError::UnfrozenVersion(version));
+        }
+        Ok(CompressedPgn {
+            version,
+            strings: Vec::new(),
+            string_ids: BTreeMap::new(),
+        })
+    }
+
+    /// Compresses a game, adding its new names to the string table.
+    ///
+    /// Fails with `NonCanonicalSan` if a move would not decompress as
+    /// written, so that decompressing the document always gives `game` back.
+    pub fn compress(&mut self, game: &PgnGame) -> Result<Vec<u8>, CompressedPgnError> {
+        let mut encoder = Encoder::with_version(self.version);
+        for mv in &game.moves {
+            encoder.encode_move(mv)?;
+        }
+        let moves = encoder.finalize();
+        let decoded = encoder.decode(&moves, game.moves.len())?;
+        if let Some((ply, (san, canonical))) = game
+            .moves
+            .iter()
+            .zip(decoded)
+            .enumerate()
+            .find(|(_, (san, canonical))| *san != canonical)
+        {
+            return Err(CompressedPgnError::NonCanonicalSan {
+                ply,
+                san: san.clone(),
+                canonical,
+            });
+        }
+        let moves = moves.to_bytes();
+
+        let mut out = Vec::with_capacity(moves.len() + 64);
+        out.push(version_to_byte(self.version));
+
+        write_varint(&mut out, game.tags.len() as u32);
+        for (name, value) in &game.tags {
+            match KNOWN_TAGS.iter().position(|known| known == name) {
+                Some(id) => out.push(id as u8 + 1),
+                None => {
+                    out.push(0);
+                    write_str(&mut out, name);
+                }
+            }
+            self.write_value(&mut out, field_coder(name), value);
+        }
+
+        write_varint(&mut out, game.moves.len() as u32);
+        write_varint(&mut out, moves.len() as u32);
+        out.extend_from_slice(&moves);
+        out.push(match game.result {
+            None => 0,
+            Some(GameResult::Unknown) => 1,
+            Some(GameResult::WhiteWins) => 2,
+            Some(GameResult::BlackWins) => 3,
+            Some(GameResult::Draw) => 4,
+        });
+        Ok(out)
+    }
+
+    /// Decompresses a game, adding its new names to the string table.
+    pub fn decompress(&mut self, bytes: &[u8]) -> Result<PgnGame, CompressedPgnError> {
+        let mut reader = Reader { bytes, pos: 0 };
+        let version = reader.byte()?;
+        let version =
+            version_from_byte(version).ok_or(CompressedPgnError::UnknownVersion(version))?;
+
+        let tag_count = reader.varint()?;
+        let mut tags = Vec::with_capacity(tag_count.min(64) as usize);
+        for _ in 0..tag_count {
+            let name = match reader.byte()? {
+                0 => reader.string()?,
+                id => KNOWN_TAGS
+                    .get(id as usize - 1)
+                    .ok_or(CompressedPgnError::UnknownTag(id))?
+                    .to_string(),
+            };
+            let value = self.read_value(&mut reader, field_coder(&name))?;
+            tags.push((name, value));
+        }
+
+        let plies = reader.varint()? as usize;
+        let len = reader.varint()? as usize;
+        let data = BitVec::from_bytes(reader.take(len)?);
+        let (moves, _) = Decoder::with_version(version).decode_with_outcome(&data, plies)?;
+
+        let result = match reader.byte()? {
+            0 => None,
+            1 => Some(GameResult::Unknown),
+            2 => Some(GameResult::WhiteWins),
+            3 => Some(GameResult::BlackWins),
+            4 => Some(GameResult::Draw),
+            _ => return Err(CompressedPgnError::InvalidResult),
+        };
+
+        Ok(PgnGame {
+            tags,
+            moves,
+            result,
+        })
+    }
+
+    fn write_value(&mut self, out: &mut Vec<u8>, coder: FieldCoder, value: &str) {
+        // Typed coders use 0 for values they cannot reproduce exactly
+        match coder {
+            FieldCoder::Date => match parse_date(value) {
+                Some((year, month, day)) => {
+                    write_varint(out, year);
+                    out.push(month);
+                    out.push(day);
+                }
+                None => {
+                    write_varint(out, 0);
+                    write_str(out, value);
+                }
+            },
+            FieldCoder::Number => match parse_number(value) {
+                Some(n) => write_varint(out, n + 1),
+                None => {
+                    write_varint(out, 0);
+                    write_str(out, value);
+                }
+            },
+            FieldCoder::TimeControl => match parse_time_control(value) {
+                Some((base, increment)) => {
+                    write_varint(out, base + 1);
+                    write_varint(out, increment.map_or(0, |inc| inc + 1));
+                }
+                None => {
+                    write_varint(out, 0);
+                    write_str(out, value);
+                }
+            },
+            FieldCoder::Table => match self.string_ids.get(value) {
+                Some(&id) => write_varint(out, id),
+                None => {
+                    let id = self.strings.len() as u32;
+                    write_varint(out, id);
+                    write_str(out, value);
+                    self.strings.push(value.to_string());
+                    self.string_ids.insert(value.to_string(), id);
+                }
+            },
+            FieldCoder::Raw => write_str(out, value),
+        }
+    }
+
+    fn read_value(
+        &mut self,
+        reader: &mut Reader<'_>,
+        coder: FieldCoder,
+    ) -> Result<String, CompressedPgnError> {
+        match coder {
+            FieldCoder::Date => match reader.varint()? {
+                0 => reader.string(),
+                year => {
+                    let month = reader.byte()?;
+                    let day = reader.byte()?;
+                    Ok(format_date(year, month, day))
+                }
+            },
+            FieldCoder::Number => match reader.varint()? {
+                0 => reader.string(),
+                n => Ok((n - 1).to_string()),
+            },
+            FieldCoder::TimeControl => match reader.varint()? {
+                0 => reader.string(),
+                base => match reader.varint()? {
+                    0 => Ok((base - 1).to_string()),
+                    increment => Ok(format!("{}+{}", base - 1, increment - 1)),
+                },
+            },
+            FieldCoder::Table => {
+                let id = reader.varint()?;
+                match id as usize {
+                    i if i < self.strings.len() => Ok(self.strings[i].clone()),
+                    i if i == self.strings.len() => {
+                        let value = reader.string()?;
+                        self.strings.push(value.clone());
+                        self.string_ids.insert(value.clone(), id);
+                        Ok(value)
+                    }
+                    _ => Err(CompressedPgnError::UnknownString(id)),
+                }
+            }
+            FieldCoder::Raw => reader.string(),
+        }
+    }
+}
+
+fn version_to_byte(version: CodecVersion) -> u8 {
+    match version {
+        CodecVersion::V1 => 1,
+        CodecVersion::V2 => 2,
+        CodecVersion::V3 => 3,
+        CodecVersion::V4 => unreachable!("unfrozen versions are refused by with_version"),
+    }
+}
+
+fn version_from_byte(byte: u8) -> Option<CodecVersion> {
+    match byte {
+        1 => Some(CodecVersion::V1),
+        2 => Some(CodecVersion::V2),
+        3 => Some(CodecVersion::V3),
+        _ => None,
+    }
+}
+
+/// Parses `YYYY.MM.DD`, where any part may be question marks, into a nonzero
+/// year code (1 for `????`, year + 2 otherwise) and month and day codes (0 for
+/// `??`, value + 1 otherwise).
+fn parse_date(value: &str) -> Option<(u32, u8, u8)> {
+    let mut parts = value.split('.');
+    let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() || year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return None;
+    }
+    let year = match parse_digits(year)? {
+        None => 1,
+        Some(year) => year + 2,
+    };
+    let month = parse_digits(month)?.map_or(0, |m| m as u8 + 1);
+    let day = parse_digits(day)?.map_or(0, |d| d as u8 + 1);
+    Some((year, month, day))
+}
+
+/// Parses a fixed-width field of digits, or of question marks for `None`.
+fn parse_digits(part: &str) -> Option<Option<u32>> {
+    if part.bytes().all(|b| b == b'?') {
+        Some(None)
+    } else if part.bytes().all(|b| b.is_ascii_digit()) {
+        part.parse().ok().map(Some)
+    } else {
+        None
+    }
+}
+
+fn format_date(year: u32, month: u8, day: u8) -> String {
+    let year = match year {
+        1 => "????".to_string(),
+        year => format!("{:04}", year - 2),
+    };
+    let part = |code: u8| match code {
+        0 => "??".to_string(),
+        code => format!("{:02}", code - 1),
+    };
+    format!("{}.{}.{}", year, part(month), part(day))
+}
+
+/// Parses a number written without sign or leading zeros.
+fn parse_number(value: &str) -> Option<u32> {
+    let n: u32 = value.parse().ok()?;
+    (n.to_string() == value && n < u32::MAX).then_some(n)
+}
+
+/// Parses `base` or `base+increment`, in seconds.
+fn parse_time_control(value: &str) -> Option<(u32, Option<u32>)> {
+    match value.split_once('+') {
+        Some((base, increment)) => Some((parse_number(base)?, Some(parse_number(increment)?))),
+        None => Some((parse_number(value)?, None)),
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_str(out: &mut Vec<u8>, value: &str) {
+    write_varint(out, value.len() as u32);
+    out.extend_from_slice(value.as_bytes());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, CompressedPgnError> {
+        let byte = *self
+            .bytes
+            .get(self.pos)
+            .ok_or(CompressedPgnError::Truncated)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CompressedPgnError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or(CompressedPgnError::Truncated)?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn varint(&mut self) -> Result<u32, CompressedPgnError> {
+        let mut value = 0u32;
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            value |= u32::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(CompressedPgnError::Truncated)
+    }
+
+    fn string(&mut self) -> Result<String, CompressedPgnError> {
+        let len = self.varint()? as usize;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| CompressedPgnError::InvalidUtf8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(tags: &[(&str, &str)], moves: &str, result: Option<GameResult>) -> PgnGame {
+        PgnGame {
+            tags: tags
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            moves: moves.split_whitespace().map(String::from).collect(),
+            result,
+        }
+    }
+
+    #[test]
+    fn test_document_roundtrip() -> Result<(), CompressedPgnError> {
+        let games = [
+            game(
+                &[
+                    ("Event", "Tata Steel Masters"),
+                    ("Site", "Wijk aan Zee NED"),
+                    ("Date", "2024.01.13"),
+                    ("Round", "1.4"),
+                    ("White", "Carlsen, Magnus"),
+                    ("Black", "Nakamura, Hikaru"),
+                    ("Result", "1/2-1/2"),
+                    ("WhiteElo", "2830"),
+                    ("BlackElo", "02789"),
+                    ("TimeControl", "5400+30"),
+                    ("EventDate", "2024.??.??"),
+                    ("Annotator", "Quote \"and\" backslash \\"),
+                    ("CustomTag", "kept verbatim"),
+                ],
+                "d4 Nf6 c4 e6 Nc3 Bb4 Qc2 O-O a3 Bxc3+ Qxc3 b6",
+                Some(GameResult::Draw),
+            ),
+            game(
+                &[
+                    ("Event", "Tata Steel Masters"),
+                    ("Date", "????.??.??"),
+                    ("White", "Nakamura, Hikaru"),
+                    ("Black", "Carlsen, Magnus"),
+                    ("TimeControl", "-"),
+                    ("UTCDate", "2024/01/14"),
+                ],
+                "e4 e5 Nf3 Nc6",
+                None,
+            ),
+            game(&[], "", Some(GameResult::Unknown)),
+        ];
+
+        for version in [CodecVersion::V1, CodecVersion::V3] {
+            let mut writer = CompressedPgn::with_version(version)?;
+            let documents = games
+                .iter()
+                .map(|g| writer.compress(g))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            // Names seen in the first game are references in the second
+            assert!(documents[1].len() < 40);
+
+            let mut reader = CompressedPgn::new();
+            for (document, original) in documents.iter().zip(&games) {
+                assert_eq!(&reader.decompress(document)?, original);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_non_canonical_moves() -> Result<(), CompressedPgnError> {
+        // Ngf3 is over-disambiguated and Qxf7 mates without saying so
+        let original = game(
+            &[("White", "A")],
+            "e4 e5 Bc4 Nc6 Qh5 Nf6 Ngf3 d6 Qxf7",
+            Some(GameResult::WhiteWins),
+        );
+        let error = CompressedPgn::new().compress(&original).unwrap_err();
+        assert!(matches!(
+            error,
+            CompressedPgnError::NonCanonicalSan { ply: 6, ref san, ref canonical }
+                if san == "Ngf3" && canonical == "Nf3"
+        ));
+
+        let mut disambiguated = original.clone();
+        disambiguated.moves[6] = "Nf3".to_string();
+        let error = CompressedPgn::new().compress(&disambiguated).unwrap_err();
+        assert!(matches!(
+            error,
+            CompressedPgnError::NonCanonicalSan { ply: 8, ref canonical, .. } if canonical == "Qxf7#"
+        ));
+
+        let mut normalized = original;
+        normalized.normalize_moves()?;
+        assert_eq!(normalized.moves[6], "Nf3");
+        assert_eq!(normalized.moves[8], "Qxf7#");
+        let document = CompressedPgn::new().compress(&normalized)?;
+        assert_eq!(CompressedPgn::new().decompress(&document)?, normalized);
+        Ok(())
+    }
+
+    #[test]
+    fn test_typed_fields_are_exact() {
+        for date in ["2024.01.13", "0999.??.??", "????.??.??", "2024.00.99"] {
+            let (year, month, day) = parse_date(date).unwrap();
+            assert_eq!(format_date(year, month, day), date);
+        }
+        for date in ["2024.1.13", "2024-01-13", "2024.01.13.", "abcd.01.01"] {
+            assert_eq!(parse_date(date), None);
+        }
+        assert_eq!(parse_number("2800"), Some(2800));
+        assert_eq!(parse_number("02800"), None);
+        assert_eq!(parse_number("+5"), None);
+        assert_eq!(parse_time_control("180+2"), Some((180, Some(2))));
+        assert_eq!(parse_time_control("40/7200:3600"), None);
+    }
+
+    #[test]
+    fn test_truncated_document() {
+        let mut writer = CompressedPgn::new();
+        let document = writer
+            .compress(&game(&[("White", "A")], "e4 e5", None))
+            .unwrap();
+        for len in 0..document.len() {
+            assert!(CompressedPgn::new().decompress(&document[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_unfrozen_version_is_refused() {
+        assert!(matches!(
+            CompressedPgn::with_version(CodecVersion::V4),
+            Err(CompressedPgnError::UnfrozenVersion(CodecVersion::V4))
+        ));
+
+        // A V4 version byte is not a stored format
+        let mut document = CompressedPgn::new()
+            .compress(&game(&[], "e4 e5", None))
+            .unwrap();
+        document[0] = 4;
+        assert!(matches!(
+            CompressedPgn::new().decompress(&document),
+            Err(CompressedPgnError::UnknownVersion(4))
+        ));
+    }
+
+    #[test]
+    fn test_display_as_pgn() {
+        let game = game(
+            &[("White", "A \"B\""), ("Black", "C")],
+            "f3 e5 g4 Qh4#",
+            Some(GameResult::BlackWins),
+        );
+        assert_eq!(
+            game.to_string(),
+            "[White \"A \\\"B\\\"\"]\n[Black \"C\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n"
+        );
+    }
+}
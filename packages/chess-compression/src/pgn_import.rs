@@ -0,0 +1,239 @@
+// src/pgn_import.rs
+//! Reading games from PGN text, shared by the database importer and the
+//! upload flow so both accept and normalize games the same way.
+
+use crate::outcome::GameResult;
+use pgn_reader::{BufferedReader, RawHeader, SanPlus, Skip, Visitor};
+use thiserror::Error;
+
+/// A game read by `Importer`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportedGame {
+    /// Every tag pair, in file order and as written.
+    pub tags: Vec<(String, String)>,
+    /// White's name, `Unknown` when given as `?`.
+    pub white: String,
+    /// Black's name, `Unknown` when given as `?`.
+    pub black: String,
+    pub white_elo: Option<i32>,
+    pub black_elo: Option<i32>,
+    /// Year, month and day. An unknown month or day reads as 1, and an invalid
+    /// date falls back to January 1st of its year.
+    pub date: Option<(i32, u32, u32)>,
+    pub eco: Option<String>,
+    pub time_control: Option<String>,
+    /// `GameResult::Unknown` only when the game has no `Result` tag.
+    pub result: GameResult,
+    /// SAN moves of the main line.
+    pub moves: Vec<String>,
+}
+
+impl ImportedGame {
+    /// Returns the value of the first tag named `name`.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Reasons a game is rejected by `Importer`.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ImportError {
+    #[error("Game result {0:?} is not a win, loss or draw")]
+    UnfinishedGame(String),
+    #[error("Game is missing a player name")]
+    MissingPlayer,
+}
+
+impl ImportError {
+    /// Returns a stable, machine-readable code for the kind of error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ImportError::UnfinishedGame(_) => "UNFINISHED_GAME",
+            ImportError::MissingPlayer => "MISSING_PLAYER",
+        }
+    }
+}
+
+/// `pgn_reader` visitor that reads the main line and headers of each game.
+///
+/// Variations and comments are skipped. Games with an unfinished result or
+/// without player names are rejected without reading their moves.
+#[derive(Debug, Default)]
+pub struct Importer {
+    game: ImportedGame,
+    error: Option<ImportError>,
+}
+
+impl Importer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn player_name(value: &str) -> String {
+    if value == "?" {
+        "Unknown".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn parse_elo(value: &[u8]) -> Option<i32> {
+    std::str::from_utf8(value).ok()?.parse().ok()
+}
+
+/// Parses a PGN date such as `2024.03.??`.
+fn parse_date(value: &str) -> Option<(i32, u32, u32)> {
+    let mut parts = value.split('.');
+    let year = parts.next()?.parse().ok()?;
+    let mut part = || match parts.next() {
+        Some("??") => Some(1),
+        Some(part) => part.parse().ok(),
+        None => None,
+    };
+    match (part(), part()) {
+        (Some(month), Some(day))
+            if (1..=12).contains(&month) && (1..=days_in_month(year, month)).contains(&day) =>
+        {
+            Some((year, month, day))
+        }
+        _ => Some((year, 1, 1)),
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl Visitor for Importer {
+    type Result = Result<ImportedGame, ImportError>;
+
+    fn begin_game(&mut self) {
+        self.game = ImportedGame::default();
+        self.error = None;
+    }
+
+    fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
+        // Tags that are not UTF-8 are kept as empty strings
+        let value_str = value.decode_utf8().unwrap_or_default();
+        match key {
+            b"White" => self.game.white = player_name(&value_str),
+            b"Black" => self.game.black = player_name(&value_str),
+            b"WhiteElo" => self.game.white_elo = parse_elo(value.as_bytes()),
+            b"BlackElo" => self.game.black_elo = parse_elo(value.as_bytes()),
+            b"Date" => self.game.date = parse_date(&value_str),
+            b"ECO" => self.game.eco = Some(value_str.to_string()),
+            b"TimeControl" => self.game.time_control = Some(value_str.to_string()),
+            b"Result" => match value_str.parse::<GameResult>() {
+                Ok(result) if result != GameResult::Unknown => self.game.result = result,
+                _ => {
+                    self.error = Some(ImportError::UnfinishedGame(value_str.to_string()));
+                }
+            },
+            _ => {}
+        }
+        self.game.tags.push((
+            String::from_utf8_lossy(key).into_owned(),
+            value_str.into_owned(),
+        ));
+    }
+
+    fn end_headers(&mut self) -> Skip {
+        if self.error.is_none() && (self.game.white.is_empty() || self.game.black.is_empty()) {
+            self.error = Some(ImportError::MissingPlayer);
+        }
+        Skip(self.error.is_some())
+    }
+
+    fn san(&mut self, san_plus: SanPlus) {
+        self.game.moves.push(san_plus.to_string());
+    }
+
+    fn begin_variation(&mut self) -> Skip {
+        Skip(true) // Skip variations
+    }
+
+    fn end_game(&mut self) -> Self::Result {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => Ok(std::mem::take(&mut self.game)),
+        }
+    }
+}
+
+/// Reads every game of a PGN text.
+pub fn import_games(pgn: &[u8]) -> Vec<Result<ImportedGame, ImportError>> {
+    let mut reader = BufferedReader::new(pgn);
+    let mut importer = Importer::new();
+    let mut games = Vec::new();
+    // Reading from memory cannot fail
+    while let Ok(Some(game)) = reader.read_game(&mut importer) {
+        games.push(game);
+    }
+    games
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PGN: &str = r#"[Event "Casual"]
+[White "?"]
+[Black "Carlsen, Magnus"]
+[Result "0-1"]
+[Date "2019.02.??"]
+[WhiteElo "?"]
+[BlackElo "2845"]
+
+1. e4 (1. d4 d5) 1... c5 2. Nf3 {A comment} d6 0-1
+
+[White "A"]
+[Black "B"]
+[Result "*"]
+
+1. d4 *
+
+[White "A"]
+[Black ""]
+[Result "1-0"]
+
+1. e4 1-0
+
+[White "A"]
+[Black "B"]
+[Date "2023.02.30"]
+
+1. c4
+"#;
+
+    #[test]
+    fn test_import_games() {
+        let games = import_games(PGN.as_bytes());
+        assert_eq!(games.len(), 4);
+
+        let game = games[0].as_ref().unwrap();
+        assert_eq!(game.white, "Unknown");
+        assert_eq!(game.black, "Carlsen, Magnus");
+        assert_eq!((game.white_elo, game.black_elo), (None, Some(2845)));
+        assert_eq!(game.date, Some((2019, 2, 1)));
+        assert_eq!(game.result, GameResult::BlackWins);
+        assert_eq!(game.moves, ["e4", "c5", "Nf3", "d6"]);
+        assert_eq!(game.tag("Event"), Some("Casual"));
+
+        assert_eq!(games[1], Err(ImportError::UnfinishedGame("*".to_string())));
+        assert_eq!(games[2], Err(ImportError::MissingPlayer));
+
+        // No result tag is accepted, and an impossible date keeps its year
+        let game = games[3].as_ref().unwrap();
+        assert_eq!(game.result, GameResult::Unknown);
+        assert_eq!(game.date, Some((2023, 1, 1)));
+    }
+}
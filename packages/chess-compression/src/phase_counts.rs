@@ -0,0 +1,35 @@
+// src/phase_counts.rs
+// Generated by `cargo run --release --example fit_phase_codes`; do not edit.
+// Corpus: none. V4 is not frozen until these are fitted on a pinned corpus.
+
+/// How often each move index was played in the positions of each bucket of
+/// `MOVE_COUNT_BOUNDS`, counting only the moves coded with a V4 codebook.
+#[rustfmt::skip]
+pub(crate) static PHASE_COUNTS: [&[u32]; 13] = [
+    &[
+    ],
+    &[
+    ],
+    &[
+    ],
+    &[
+    ],
+    &[
+    ],
+    &[
+    ],
+    &[
+    ],
+    &[
+    ],
+    &[
+    ],
+    &[
+    ],
+    &[
+    ],
+    &[
+    ],
+    &[
+    ],
+];
@@ -1,66 +1,72 @@
 // src/psqt.rs
-use shakmaty::{Chess, Color, Position, Role, Square};
-use std::sync::OnceLock;
+use shakmaty::{Color, Role, Square};
 
-// Define the PSQT as a static OnceLock
-static PSQT: OnceLock<[[i32; 64]; 6]> = OnceLock::new();
+// Piece-square tables indexed by `role as usize - 1`, written rank 8 first, so
+// square `sq` of a White piece reads entry `sq ^ 56` and of a Black piece entry `sq`
+static PSQT: [[i32; 64]; 6] = [
+    // Pawn
+    [
+        0, 0, 0, 0, 0, 0, 0, 0, 50, 50, 50, 50, 50, 50, 50, 50, 10, 10, 20, 30, 30, 20, 10, 10, 5,
+        5, 10, 25, 25, 10, 5, 5, 0, 0, 0, 20, 21, 0, 0, 0, 5, -5, -10, 0, 0, -10, -5, 5, 5, 10, 10,
+        -31, -31, 10, 10, 5, 0, 0, 0, 0, 0, 0, 0, 0,
+    ],
+    // Knight
+    [
+        -50, -40, -30, -30, -30, -30, -40, -50, -40, -20, 0, 0, 0, 0, -20, -40, -30, 0, 10, 15, 15,
+        10, 0, -30, -30, 5, 15, 20, 20, 15, 5, -30, -30, 0, 15, 20, 20, 15, 0, -30, -30, 5, 10, 15,
+        15, 11, 5, -30, -40, -20, 0, 5, 5, 0, -20, -40, -50, -40, -30, -30, -30, -30, -40, -50,
+    ],
+    // Bishop
+    [
+        -20, -10, -10, -10, -10, -10, -10, -20, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 10, 10, 5,
+        0, -10, -10, 5, 5, 10, 10, 5, 5, -10, -10, 0, 10, 10, 10, 10, 0, -10, -10, 10, 10, 10, 10,
+        10, 10, -10, -10, 5, 0, 0, 0, 0, 5, -10, -20, -10, -10, -10, -10, -10, -10, -20,
+    ],
+    // Rook
+    [
+        0, 0, 0, 0, 0, 0, 0, 0, 5, 10, 10, 10, 10, 10, 10, 5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0,
+        0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0,
+        -5, 0, 0, 0, 5, 5, 0, 0, 0,
+    ],
+    // Queen
+    [
+        -20, -10, -10, -5, -5, -10, -10, -20, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 5, 5, 5, 0,
+        -10, -5, 0, 5, 5, 5, 5, 0, -5, 0, 0, 5, 5, 5, 5, 0, -5, -10, 5, 5, 5, 5, 5, 0, -10, -10, 0,
+        5, 0, 0, 0, 0, -10, -20, -10, -10, -5, -5, -10, -10, -20,
+    ],
+    // King
+    [
+        -30, -40, -40, -50, -50, -40, -40, -30, -30, -40, -40, -50, -50, -40, -40, -30, -30, -40,
+        -40, -50, -50, -40, -40, -30, -30, -40, -40, -50, -50, -40, -40, -30, -20, -30, -30, -40,
+        -40, -30, -30, -20, -10, -20, -20, -20, -20, -20, -20, -10, 20, 20, 0, 0, 0, 0, 20, 20, 0,
+        30, 10, 0, 0, 10, 30, 0,
+    ],
+];
 
+/// PSQT gain of moving `role` from `from` to `to` for `color`.
 #[inline]
-pub fn get_psqt() -> &'static [[i32; 64]; 6] {
-    PSQT.get_or_init(|| {
-        [
-            // Pawn
-            [
-                0, 0, 0, 0, 0, 0, 0, 0, 50, 50, 50, 50, 50, 50, 50, 50, 10, 10, 20, 30, 30, 20, 10,
-                10, 5, 5, 10, 25, 25, 10, 5, 5, 0, 0, 0, 20, 21, 0, 0, 0, 5, -5, -10, 0, 0, -10,
-                -5, 5, 5, 10, 10, -31, -31, 10, 10, 5, 0, 0, 0, 0, 0, 0, 0, 0,
-            ],
-            // Knight
-            [
-                -50, -40, -30, -30, -30, -30, -40, -50, -40, -20, 0, 0, 0, 0, -20, -40, -30, 0, 10,
-                15, 15, 10, 0, -30, -30, 5, 15, 20, 20, 15, 5, -30, -30, 0, 15, 20, 20, 15, 0, -30,
-                -30, 5, 10, 15, 15, 11, 5, -30, -40, -20, 0, 5, 5, 0, -20, -40, -50, -40, -30, -30,
-                -30, -30, -40, -50,
-            ],
-            // Bishop
-            [
-                -20, -10, -10, -10, -10, -10, -10, -20, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 10,
-                10, 5, 0, -10, -10, 5, 5, 10, 10, 5, 5, -10, -10, 0, 10, 10, 10, 10, 0, -10, -10,
-                10, 10, 10, 10, 10, 10, -10, -10, 5, 0, 0, 0, 0, 5, -10, -20, -10, -10, -10, -10,
-                -10, -10, -20,
-            ],
-            // Rook
-            [
-                0, 0, 0, 0, 0, 0, 0, 0, 5, 10, 10, 10, 10, 10, 10, 5, -5, 0, 0, 0, 0, 0, 0, -5, -5,
-                0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0,
-                0, 0, 0, 0, -5, 0, 0, 0, 5, 5, 0, 0, 0,
-            ],
-            // Queen
-            [
-                -20, -10, -10, -5, -5, -10, -10, -20, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 5, 5,
-                5, 0, -10, -5, 0, 5, 5, 5, 5, 0, -5, 0, 0, 5, 5, 5, 5, 0, -5, -10, 5, 5, 5, 5, 5,
-                0, -10, -10, 0, 5, 0, 0, 0, 0, -10, -20, -10, -10, -5, -5, -10, -10, -20,
-            ],
-            // King
-            [
-                -30, -40, -40, -50, -50, -40, -40, -30, -30, -40, -40, -50, -50, -40, -40, -30,
-                -30, -40, -40, -50, -50, -40, -40, -30, -30, -40, -40, -50, -50, -40, -40, -30,
-                -20, -30, -30, -40, -40, -30, -30, -20, -10, -20, -20, -20, -20, -20, -20, -10, 20,
-                20, 0, 0, 0, 0, 20, 20, 0, 30, 10, 0, 0, 10, 30, 0,
-            ],
-        ]
-    })
+pub fn psqt_delta(color: Color, role: Role, from: Square, to: Square) -> i32 {
+    let table = &PSQT[role as usize - 1];
+    match color {
+        Color::White => {
+            table[mirror_square(to as u8) as usize] - table[mirror_square(from as u8) as usize]
+        }
+        Color::Black => table[to as usize] - table[from as usize],
+    }
 }
 
+/// PSQT gain of moving `role` from `from` to `to` for `color`, as V1 ranks it.
+///
+/// Both sides read the table upside down; changing this changes the V1 format.
 #[inline]
-pub fn piece_value(board: &Chess, role: Role, square: Square) -> i32 {
-    let us = board.turn();
-    let index = if us == Color::White {
-        square as u8
-    } else {
-        mirror_square(square as u8)
-    };
-    get_psqt()[role as usize - 1][index as usize]
+pub fn psqt_delta_v1(color: Color, role: Role, from: Square, to: Square) -> i32 {
+    let table = &PSQT[role as usize - 1];
+    match color {
+        Color::White => table[to as usize] - table[from as usize],
+        Color::Black => {
+            table[mirror_square(to as u8) as usize] - table[mirror_square(from as u8) as usize]
+        }
+    }
 }
 
 #[inline]
@@ -0,0 +1,406 @@
+// src/ranking.rs
+use crate::psqt::{psqt_delta, psqt_delta_v1};
+use shakmaty::{attacks, Bitboard, Chess, Color, Move, MoveList, Position, Role, Square};
+
+/// Move scoring model, selected by the codec version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RankingModel {
+    V1,
+    V2,
+}
+
+/// Ranks the legal moves of a position without allocating.
+///
+/// Each legal move gets a packed sort key: the move score in the high bits and
+/// the inverted move-list index in the low byte, so keys are unique and
+/// descending key order matches a stable descending sort by score.
+pub struct MoveRanker {
+    model: RankingModel,
+    moves: MoveList,
+    keys: [i64; 256],
+}
+
+impl MoveRanker {
+    pub fn new(model: RankingModel) -> Self {
+        MoveRanker {
+            model,
+            moves: MoveList::new(),
+            keys: [0; 256],
+        }
+    }
+
+    /// Generates and scores the legal moves of `board`.
+    ///
+    /// `last_move` is the move that led to `board`, if any. It is ignored by V1.
+    pub fn fill(&mut self, board: &Chess, last_move: Option<&Move>) {
+        self.moves = board.legal_moves();
+
+        match self.model {
+            RankingModel::V1 => {
+                let us = board.turn();
+                let them = us.other();
+                // Only black pawns are considered defenders; changing this changes the format
+                let defenders = board.board().pawns() & board.board().black();
+
+                for (i, mv) in self.moves.iter().enumerate() {
+                    let score = score_v1(us, them, defenders, mv);
+                    self.keys[i] = (i64::from(score) << 8) | (255 - i as i64);
+                }
+            }
+            RankingModel::V2 => {
+                let ctx = ContextV2::new(board, last_move);
+
+                for (i, mv) in self.moves.iter().enumerate() {
+                    let score = score_v2(board, &ctx, mv);
+                    self.keys[i] = (i64::from(score) << 8) | (255 - i as i64);
+                }
+            }
+        }
+    }
+
+    /// Returns the number of legal moves scored by the last `fill`.
+    pub fn move_count(&self) -> usize {
+        self.moves.len()
+    }
+
+    /// Returns the rank of `mv` among the scored moves, 0 being the best.
+    pub fn rank_of(&self, mv: &Move) -> Option<u32> {
+        let i = self.moves.iter().position(|m| m == mv)?;
+        let key = self.keys[i];
+        let rank = self.keys[..self.moves.len()]
+            .iter()
+            .filter(|&&k| k > key)
+            .count();
+        Some(rank as u32)
+    }
+
+    /// Returns the move with the given rank, reordering the keys in place.
+    pub fn select(&mut self, rank: usize) -> Option<&Move> {
+        let keys = &mut self.keys[..self.moves.len()];
+        if rank >= keys.len() {
+            return None;
+        }
+        let (_, key, _) = keys.select_nth_unstable_by(rank, |a, b| b.cmp(a));
+        let i = 255 - (*key & 0xff) as usize;
+        self.moves.get(i)
+    }
+}
+
+fn score_v1(us: Color, them: Color, defenders: Bitboard, mv: &Move) -> i32 {
+    let piece_role = mv.role();
+    let from_square = mv.from().unwrap_or(Square::A1); // Use A1 for drops
+    let to_square = mv.to();
+
+    let mut score = 0;
+
+    if let Some(promotion_role) = mv.promotion() {
+        score += (promotion_role as i32) << 26;
+    }
+
+    if mv.is_capture() {
+        score += 1 << 25;
+    }
+
+    let defending_pawns = attacks::pawn_attacks(them, to_square) & defenders;
+
+    let defending_pawns_score = if defending_pawns.0 == 0 {
+        6
+    } else {
+        5 - piece_role as i32
+    } << 22;
+    score += defending_pawns_score;
+
+    let move_value = psqt_delta_v1(us, piece_role, from_square, to_square);
+    score += (512 + move_value) << 12;
+
+    score += (to_square as i32) << 6;
+    score += from_square as i32;
+
+    score
+}
+
+/// Per-position data shared by every move scored with V2.
+struct ContextV2 {
+    us: Color,
+    defenders: Bitboard,
+    their_king: Option<Square>,
+    recapture_square: Option<Square>,
+}
+
+impl ContextV2 {
+    fn new(board: &Chess, last_move: Option<&Move>) -> Self {
+        let us = board.turn();
+        let them = us.other();
+        ContextV2 {
+            us,
+            defenders: board.board().pawns() & board.board().by_color(them),
+            their_king: board.board().king_of(them),
+            recapture_square: last_move.filter(|mv| mv.is_capture()).map(Move::to),
+        }
+    }
+}
+
+// V2 move classes, best first
+const BAND_QUEEN_PROMOTION: i32 = 9;
+const BAND_WINNING_CAPTURE: i32 = 8;
+const BAND_EVEN_CAPTURE: i32 = 7;
+const BAND_SAFE_CHECK: i32 = 6;
+const BAND_SAFE_QUIET: i32 = 5;
+const BAND_DEFENDED_PAWN: i32 = 4;
+const BAND_LOSING_CAPTURE: i32 = 3;
+const BAND_DEFENDED_MINOR: i32 = 2;
+const BAND_DEFENDED_MAJOR: i32 = 1;
+const BAND_UNDERPROMOTION: i32 = 0;
+
+// A V2 score packs the band above a signed 12-bit value, wide enough for any
+// SEE gain (a queen promotion capturing a queen is worth 1700), and the squares
+const BAND_SHIFT: i32 = 24;
+const VALUE_LIMIT: i32 = 1 << 11;
+
+fn score_v2(board: &Chess, ctx: &ContextV2, mv: &Move) -> i32 {
+    let role = mv.role();
+    let from = mv.from().unwrap_or(Square::A1);
+    let to = mv.to();
+    // Castling moves are encoded king-takes-rook; rank them by the king's target
+    let king_to = mv.castling_side().map_or(to, |side| side.king_to(ctx.us));
+
+    let (band, value) = match mv.promotion() {
+        Some(Role::Queen) => (BAND_QUEEN_PROMOTION, see(board, mv)),
+        Some(_) => (BAND_UNDERPROMOTION, see(board, mv)),
+        None if mv.is_capture() => {
+            let gain = see(board, mv);
+            let band = if gain > 0 || (gain == 0 && ctx.recapture_square == Some(to)) {
+                BAND_WINNING_CAPTURE
+            } else if gain == 0 {
+                BAND_EVEN_CAPTURE
+            } else {
+                BAND_LOSING_CAPTURE
+            };
+            (band, gain)
+        }
+        None => {
+            let defended = (attacks::pawn_attacks(ctx.us, king_to) & ctx.defenders).any();
+            let band = match role {
+                _ if !defended && gives_check(board, ctx, mv) => BAND_SAFE_CHECK,
+                _ if !defended => BAND_SAFE_QUIET,
+                Role::Pawn => BAND_DEFENDED_PAWN,
+                Role::Knight | Role::Bishop => BAND_DEFENDED_MINOR,
+                _ => BAND_DEFENDED_MAJOR,
+            };
+            (band, psqt_delta(ctx.us, role, from, king_to))
+        }
+    };
+
+    let value = VALUE_LIMIT + value.clamp(-VALUE_LIMIT, VALUE_LIMIT - 1);
+    (band << BAND_SHIFT) + (value << 12) + ((to as i32) << 6) + from as i32
+}
+
+/// Returns whether `mv` gives check, without playing it for ordinary moves.
+fn gives_check(board: &Chess, ctx: &ContextV2, mv: &Move) -> bool {
+    let Some(king) = ctx.their_king else {
+        return false;
+    };
+    match *mv {
+        Move::Normal {
+            role,
+            from,
+            to,
+            promotion,
+            ..
+        } => {
+            let occupied = (board.board().occupied() ^ from) | to;
+            let piece = promotion.unwrap_or(role).of(ctx.us);
+            let direct = attacks::attacks(to, piece, occupied).contains(king);
+            let discovered = (board.board().attacks_to(king, ctx.us, occupied)
+                & board.board().sliders()
+                & !Bitboard::from(from))
+            .any();
+            direct || discovered
+        }
+        _ => {
+            let mut after = board.clone();
+            after.play_unchecked(mv);
+            after.is_check()
+        }
+    }
+}
+
+#[inline]
+fn see_value(role: Role) -> i32 {
+    match role {
+        Role::Pawn => 100,
+        Role::Knight => 320,
+        Role::Bishop => 330,
+        Role::Rook => 500,
+        Role::Queen => 900,
+        Role::King => 20_000,
+    }
+}
+
+/// Static exchange evaluation of the material won by `mv` on its target square.
+fn see(board: &Chess, mv: &Move) -> i32 {
+    let (Some(from), to) = (mv.from(), mv.to()) else {
+        return 0;
+    };
+    let b = board.board();
+
+    let mut gain = [0i32; 32];
+    gain[0] = mv.capture().map_or(0, see_value);
+    let mut attacker_value = see_value(mv.role());
+    if let Some(promotion) = mv.promotion() {
+        gain[0] += see_value(promotion) - see_value(Role::Pawn);
+        attacker_value = see_value(promotion);
+    }
+
+    let mut occupied = b.occupied() ^ from;
+    if let Move::EnPassant { .. } = mv {
+        occupied ^= Square::from_coords(to.file(), from.rank());
+    }
+    let mut side = board.turn().other();
+    let mut depth = 0;
+
+    loop {
+        let attackers = b.attacks_to(to, side, occupied) & occupied;
+        let Some((square, role)) = Role::ALL
+            .iter()
+            .find_map(|&role| (attackers & b.by_role(role)).first().map(|sq| (sq, role)))
+        else {
+            break;
+        };
+
+        if depth + 1 == gain.len() {
+            break;
+        }
+        depth += 1;
+        gain[depth] = attacker_value - gain[depth - 1];
+
+        occupied ^= square;
+        attacker_value = see_value(role);
+        side = side.other();
+    }
+
+    while depth > 0 {
+        gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+        depth -= 1;
+    }
+
+    gain[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::{fen::Fen, uci::UciMove, CastlingMode};
+
+    fn position(fen: &str) -> Chess {
+        Fen::from_ascii(fen.as_bytes())
+            .unwrap()
+            .into_position(CastlingMode::Standard)
+            .unwrap()
+    }
+
+    fn uci(board: &Chess, uci: &str) -> Move {
+        uci.parse::<UciMove>().unwrap().to_move(board).unwrap()
+    }
+
+    #[test]
+    fn test_see_undefended_capture() {
+        let board = position("4k3/8/8/3p4/8/8/8/3QK3 w - - 0 1");
+        assert_eq!(see(&board, &uci(&board, "d1d5")), 100);
+    }
+
+    #[test]
+    fn test_see_defended_capture() {
+        let board = position("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1");
+        assert_eq!(see(&board, &uci(&board, "d1d5")), 100 - 900);
+    }
+
+    #[test]
+    fn test_see_xray_recapture() {
+        // The rook behind the first one recaptures after the pawn takes back
+        let board = position("4k3/8/4p3/3p4/8/8/3R4/3RK3 w - - 0 1");
+        assert_eq!(see(&board, &uci(&board, "d2d5")), 100 - 500 + 100);
+
+        // A black rook on the file makes the second recapture a loss, so White stops
+        let board = position("3rk3/8/4p3/3p4/8/8/3R4/3RK3 w - - 0 1");
+        assert_eq!(see(&board, &uci(&board, "d2d5")), 100 - 500);
+    }
+
+    #[test]
+    fn test_gives_check_direct_and_discovered() {
+        let board = position("4k3/8/8/8/8/8/4B3/4RK2 w - - 0 1");
+        let ctx = ContextV2::new(&board, None);
+        assert!(gives_check(&board, &ctx, &uci(&board, "e2b5")));
+        assert!(gives_check(&board, &ctx, &uci(&board, "e2h5")));
+        assert!(!gives_check(&board, &ctx, &uci(&board, "e1d1")));
+    }
+
+    #[test]
+    fn test_v2_pawn_defence_is_side_aware() {
+        // A black pawn on d5 covers c4 and e4 but not c3
+        let board = position("4k3/8/8/3p4/8/8/8/1N2K3 w - - 0 1");
+        let ctx = ContextV2::new(&board, None);
+        let c3_band = score_v2(&board, &ctx, &uci(&board, "b1c3")) >> BAND_SHIFT;
+        let d2_band = score_v2(&board, &ctx, &uci(&board, "b1d2")) >> BAND_SHIFT;
+        assert_eq!(c3_band, BAND_SAFE_QUIET);
+        assert_eq!(d2_band, BAND_SAFE_QUIET);
+
+        // On d4 it covers c3; V1 only noticed this when Black was to move
+        let board = position("4k3/8/8/8/3p4/8/8/1N2K3 w - - 0 1");
+        let ctx = ContextV2::new(&board, None);
+        let c3_band = score_v2(&board, &ctx, &uci(&board, "b1c3")) >> BAND_SHIFT;
+        assert_eq!(c3_band, BAND_DEFENDED_MINOR);
+    }
+
+    #[test]
+    fn test_v2_psqt_is_oriented_per_side() {
+        // Central pawn advances gain for both sides, and retreating a knight loses
+        assert_eq!(
+            psqt_delta(Color::White, Role::Pawn, Square::E2, Square::E4),
+            52
+        );
+        assert_eq!(
+            psqt_delta(Color::Black, Role::Pawn, Square::E7, Square::E5),
+            52
+        );
+        assert!(psqt_delta(Color::White, Role::Knight, Square::F3, Square::G1) < 0);
+        assert!(psqt_delta(Color::Black, Role::Knight, Square::F6, Square::G8) < 0);
+
+        // V1 keeps reading the table upside down
+        assert_eq!(
+            psqt_delta_v1(Color::White, Role::Pawn, Square::E2, Square::E4),
+            -25
+        );
+
+        // A pawn reaching the 7th rank outranks an edge pawn leaving home
+        let board = position("4k3/8/1P6/8/8/8/P7/4K3 w - - 0 1");
+        let ctx = ContextV2::new(&board, None);
+        let quiet = |uci_move| score_v2(&board, &ctx, &uci(&board, uci_move));
+        assert!(quiet("b6b7") > quiet("a2a3"));
+    }
+
+    #[test]
+    fn test_v2_large_gains_keep_their_order() {
+        // Both promotions win more than 511; taking the knight on the way wins more
+        let board = position("n6k/1P6/8/8/8/8/8/4K3 w - - 0 1");
+        let ctx = ContextV2::new(&board, None);
+        let capture = score_v2(&board, &ctx, &uci(&board, "b7a8q"));
+        let push = score_v2(&board, &ctx, &uci(&board, "b7b8q"));
+        assert_eq!(capture >> BAND_SHIFT, BAND_QUEEN_PROMOTION);
+        assert_eq!(push >> BAND_SHIFT, BAND_QUEEN_PROMOTION);
+        assert!(capture > push);
+    }
+
+    #[test]
+    fn test_rank_and_select_agree() {
+        let board = position("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+        for model in [RankingModel::V1, RankingModel::V2] {
+            let mut ranker = MoveRanker::new(model);
+            for mv in board.legal_moves() {
+                ranker.fill(&board, None);
+                let rank = ranker.rank_of(&mv).unwrap();
+                assert_eq!(ranker.select(rank as usize), Some(&mv));
+            }
+        }
+    }
+}
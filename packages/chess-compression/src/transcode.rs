@@ -0,0 +1,179 @@
+// src/transcode.rs
+use crate::decoder::Decoder;
+use crate::fen_compress::CompressedPosition;
+use crate::pgn_compress::{CodecVersion, Encoder, EncoderError};
+use alloc::{string::String, vec::Vec};
+use bit_vec::BitVec;
+use core::ops::Range;
+use shakmaty::Chess;
+
+/// Which positions a transcode returns, and the codec version of the game.
+///
+/// Plies are counted from the start of the game: ply 0 is the initial
+/// position and ply `n` the position after `n` moves. By default every
+/// position after a move is returned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranscodeOptions {
+    version: CodecVersion,
+    plies: Range<usize>,
+    stride: usize,
+}
+
+impl Default for TranscodeOptions {
+    fn default() -> Self {
+        TranscodeOptions {
+            version: CodecVersion::default(),
+            plies: 1..usize::MAX,
+            stride: 1,
+        }
+    }
+}
+
+impl TranscodeOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn version(mut self, version: CodecVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Only returns positions whose ply is in `plies`.
+    pub fn ply_range(mut self, plies: Range<usize>) -> Self {
+        self.plies = plies;
+        self
+    }
+
+    /// Returns every `stride`th position of the ply range, starting with its
+    /// first ply. A stride of 0 is treated as 1.
+    pub fn stride(mut self, stride: usize) -> Self {
+        self.stride = stride.max(1);
+        self
+    }
+
+    fn samples(&self, ply: usize) -> bool {
+        self.plies.contains(&ply) && (ply - self.plies.start).is_multiple_of(self.stride)
+    }
+}
+
+/// A compressed position and the ply it was reached at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampledPosition {
+    pub ply: usize,
+    pub position: [u8; 32],
+}
+
+/// A compressed game together with its sampled positions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranscodedGame {
+    pub compressed: Vec<u8>,
+    pub positions: Vec<SampledPosition>,
+}
+
+fn sample(
+    positions: &mut Vec<SampledPosition>,
+    options: &TranscodeOptions,
+    ply: usize,
+    board: &Chess,
+) {
+    if options.samples(ply) {
+        positions.push(SampledPosition {
+            ply,
+            position: CompressedPosition::compress(board),
+        });
+    }
+}
+
+/// Compresses a game given as SAN moves and its positions in a single replay.
+pub fn transcode_moves(
+    moves: &[String],
+    options: &TranscodeOptions,
+) -> Result<TranscodedGame, EncoderError> {
+    let mut encoder = Encoder::with_version(options.version);
+    let mut positions = Vec::new();
+    sample(&mut positions, options, 0, encoder.position());
+    for mv in moves {
+        encoder.encode_move(mv)?;
+        sample(&mut positions, options, encoder.plies(), encoder.position());
+    }
+    Ok(TranscodedGame {
+        compressed: encoder.finalize().to_bytes(),
+        positions,
+    })
+}
+
+/// Extracts the positions of a compressed game.
+///
+/// Decoding stops at the end of the ply range, so sampling the opening of a
+/// long game only decodes its first moves.
+pub fn transcode_compressed(
+    compressed: &[u8],
+    plies: usize,
+    options: &TranscodeOptions,
+) -> Result<TranscodedGame, EncoderError> {
+    let data = BitVec::from_bytes(compressed);
+    let decoder = Decoder::with_version(options.version);
+    let last_ply = options.plies.end.saturating_sub(1);
+    let mut moves = decoder.decode_iter(&data, plies);
+    let mut positions = Vec::new();
+    sample(&mut positions, options, 0, moves.position());
+    while moves.ply() < last_ply {
+        if moves.next().transpose()?.is_none() {
+            break;
+        }
+        sample(&mut positions, options, moves.ply(), moves.position());
+    }
+    Ok(TranscodedGame {
+        compressed: compressed.to_vec(),
+        positions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compress_pgn_with_version;
+
+    fn moves(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_transcode_moves_and_compressed_agree() -> Result<(), EncoderError> {
+        let game = moves("e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Be3 e5 Nb3 Be6 f3 h5");
+
+        for version in [CodecVersion::V1, CodecVersion::V3] {
+            let options = TranscodeOptions::new().version(version);
+            let from_moves = transcode_moves(&game, &options)?;
+            assert_eq!(
+                from_moves.compressed,
+                compress_pgn_with_version(&game, version)?
+            );
+            assert_eq!(from_moves.positions.len(), game.len());
+
+            let from_compressed =
+                transcode_compressed(&from_moves.compressed, game.len(), &options)?;
+            assert_eq!(from_compressed, from_moves);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_transcode_range_and_stride() -> Result<(), EncoderError> {
+        let game = moves("d4 Nf6 c4 e6 Nc3 Bb4 Qc2 O-O a3 Bxc3+ Qxc3 b6");
+        let options = TranscodeOptions::new().ply_range(0..7).stride(3);
+
+        let transcoded = transcode_moves(&game, &options)?;
+        let plies: Vec<_> = transcoded.positions.iter().map(|p| p.ply).collect();
+        assert_eq!(plies, [0, 3, 6]);
+        assert_eq!(
+            transcoded.positions[0].position,
+            CompressedPosition::compress(&Chess::default())
+        );
+
+        let from_compressed = transcode_compressed(&transcoded.compressed, game.len(), &options)?;
+        assert_eq!(from_compressed.positions, transcoded.positions);
+        Ok(())
+    }
+}
@@ -1,21 +1,180 @@
 use std::str::FromStr;
 
-use crate::{compress_pgn, compress_position, decompress_pgn, decompress_position};
-use js_sys::Uint8Array; // Use this type for better TS compatibility
+use crate::pgn_compress::san_to_move;
+use crate::{
+    compress_pgn, compress_pgn_with_outcome, compress_position, decompress_pgn_movetext,
+    decompress_pgn_movetext_with_version, decompress_position, eval, import_games, transcode_moves,
+    CodecVersion, CompressedPositionError, Decoder, EncoderError, Explorer, ExplorerGame,
+    ExplorerStats, GameOutcome, GameResult, ImportError, ImportedGame, Termination,
+    TranscodeOptions,
+};
+use js_sys::{Reflect, Uint8Array}; // Use this type for better TS compatibility
 use wasm_bindgen::prelude::*;
 
+use bit_vec::BitVec;
 use shakmaty::fen::Fen;
-use shakmaty::{CastlingMode, EnPassantMode};
+use shakmaty::san::SanPlus;
+use shakmaty::uci::UciMove;
+use shakmaty::zobrist::{Zobrist64, ZobristHash};
+use shakmaty::{CastlingMode, Chess, EnPassantMode, Move, Position};
 
-#[wasm_bindgen]
-pub fn wasm_compress_position(fen: &str) -> Result<Uint8Array, JsValue> {
-    // Parse the FEN string
-    let fen = Fen::from_str(fen).map_err(|e| JsValue::from_str(&e.to_string()))?;
+#[wasm_bindgen(inline_js = r#"
+export class ChessCompressionError extends Error {
+    constructor(message, code, ply, san, fen, index) {
+        super(message);
+        this.name = "ChessCompressionError";
+        this.code = code;
+        this.ply = ply;
+        this.san = san;
+        this.fen = fen;
+        this.index = index;
+    }
+}
+"#)]
+extern "C" {
+    /// The JS `Error` subclass thrown by every binding.
+    #[wasm_bindgen(extends = js_sys::Error)]
+    pub type ChessCompressionError;
+
+    #[wasm_bindgen(constructor)]
+    fn new(
+        message: &str,
+        code: &str,
+        ply: JsValue,
+        san: JsValue,
+        fen: JsValue,
+        index: JsValue,
+    ) -> ChessCompressionError;
+}
+
+/// Error returned by the bindings, thrown in JS as a `ChessCompressionError`
+/// with the same fields. Missing fields are `null`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WasmError {
+    /// Stable, machine-readable kind of error, such as `INVALID_FEN`.
+    pub code: &'static str,
+    pub message: String,
+    /// Zero-based index of the failing move.
+    pub ply: Option<usize>,
+    /// The failing move as given by the caller.
+    pub san: Option<String>,
+    /// The position the error happened in.
+    pub fen: Option<String>,
+    /// Index of the failing item in a batch.
+    pub index: Option<usize>,
+}
+
+impl WasmError {
+    fn new(code: &'static str, message: impl ToString) -> Self {
+        WasmError {
+            code,
+            message: message.to_string(),
+            ply: None,
+            san: None,
+            fen: None,
+            index: None,
+        }
+    }
+
+    fn with_fen(mut self, fen: &str) -> Self {
+        self.fen = Some(fen.to_string());
+        self
+    }
+
+    fn with_move(mut self, mv: &str) -> Self {
+        self.san = Some(mv.to_string());
+        self
+    }
+
+    fn with_index(mut self, index: usize) -> Self {
+        self.index = Some(index);
+        self
+    }
+}
 
-    // Convert FEN to Position
-    let position = fen
+impl From<EncoderError> for WasmError {
+    fn from(error: EncoderError) -> Self {
+        let context = error.context().clone();
+        WasmError {
+            code: error.code(),
+            message: error.to_string(),
+            ply: Some(context.ply),
+            san: context.san,
+            fen: Some(context.fen),
+            index: None,
+        }
+    }
+}
+
+impl From<CompressedPositionError> for WasmError {
+    fn from(error: CompressedPositionError) -> Self {
+        WasmError::new("INVALID_COMPRESSED_POSITION", error)
+    }
+}
+
+impl From<ImportError> for WasmError {
+    fn from(error: ImportError) -> Self {
+        WasmError::new(error.code(), error)
+    }
+}
+
+impl From<WasmError> for JsValue {
+    fn from(error: WasmError) -> Self {
+        let optional = |value: Option<String>| value.map_or(JsValue::NULL, JsValue::from);
+        let number = |value: Option<usize>| {
+            value.map_or(JsValue::NULL, |value| JsValue::from_f64(value as f64))
+        };
+        ChessCompressionError::new(
+            &error.message,
+            error.code,
+            number(error.ply),
+            optional(error.san),
+            optional(error.fen),
+            number(error.index),
+        )
+        .into()
+    }
+}
+
+fn parse_fen(fen: &str) -> Result<Chess, WasmError> {
+    Fen::from_str(fen)
+        .map_err(|e| WasmError::new("INVALID_FEN", e).with_fen(fen))?
         .into_position(CastlingMode::Standard)
-        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        .map_err(|e| WasmError::new("INVALID_POSITION", e).with_fen(fen))
+}
+
+fn to_fen(position: &Chess) -> String {
+    Fen::from_position(position.clone(), EnPassantMode::Legal).to_string()
+}
+
+fn parse_uci(position: &Chess, uci: &str) -> Result<Move, WasmError> {
+    let error = |code, message: &dyn ToString| {
+        WasmError::new(code, message.to_string())
+            .with_move(uci)
+            .with_fen(&to_fen(position))
+    };
+    UciMove::from_ascii(uci.as_bytes())
+        .map_err(|e| error("INVALID_UCI", &e))?
+        .to_move(position)
+        .map_err(|e| error("ILLEGAL_MOVE", &e))
+}
+
+fn parse_san(position: &Chess, san: &str) -> Result<Move, WasmError> {
+    let error = |code, message: &dyn ToString| {
+        WasmError::new(code, message.to_string())
+            .with_move(san)
+            .with_fen(&to_fen(position))
+    };
+    san.parse::<SanPlus>()
+        .map_err(|e| error("SAN_PARSE", &e))?
+        .san
+        .to_move(position)
+        .map_err(|e| error("ILLEGAL_MOVE", &e))
+}
+
+#[wasm_bindgen]
+pub fn wasm_compress_position(fen: &str) -> Result<Uint8Array, WasmError> {
+    let position = parse_fen(fen)?;
 
     // Compress the position
     let compressed = compress_position(&position);
@@ -25,21 +184,1013 @@ pub fn wasm_compress_position(fen: &str) -> Result<Uint8Array, JsValue> {
 }
 
 #[wasm_bindgen]
-pub fn wasm_decompress_position(compressed: &[u8]) -> Result<String, JsValue> {
-    let position = decompress_position(compressed.try_into().unwrap())
-        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+pub fn wasm_decompress_position(compressed: &[u8]) -> Result<String, WasmError> {
+    let compressed = compressed.try_into().map_err(|_| {
+        WasmError::new(
+            "INVALID_LENGTH",
+            format!(
+                "Compressed position must be 32 bytes, got {}",
+                compressed.len()
+            ),
+        )
+    })?;
+    let position = decompress_position(compressed)?;
     Ok(Fen::from_position(position, EnPassantMode::Legal).to_string())
 }
 
+/// Static evaluation of a FEN position in centipawns, positive when White is better.
 #[wasm_bindgen]
-pub fn wasm_compress_pgn(moves: &str) -> Result<Uint8Array, JsValue> {
-    let moves: Vec<String> = moves.split_whitespace().map(String::from).collect();
-    let compressed = compress_pgn(&moves).map_err(|e| JsValue::from_str(&e.to_string()))?;
+pub fn wasm_evaluate(fen: &str) -> Result<i32, WasmError> {
+    Ok(eval::evaluate(&parse_fen(fen)?))
+}
+
+#[wasm_bindgen]
+pub fn wasm_compress_pgn(moves: &str) -> Result<Uint8Array, WasmError> {
+    let compressed = compress_movetext(moves)?;
     Ok(Uint8Array::from(compressed.as_slice()))
 }
 
+// Compresses space-separated moves, storing a trailing result token as the outcome
+fn compress_movetext(moves: &str) -> Result<Vec<u8>, EncoderError> {
+    let mut moves: Vec<String> = moves.split_whitespace().map(String::from).collect();
+
+    // A trailing result token is stored as the game's outcome
+    let result = moves
+        .last()
+        .and_then(|token| token.parse::<GameResult>().ok());
+    match result {
+        Some(result) => {
+            moves.pop();
+            let outcome = GameOutcome::new(result, Termination::Unknown);
+            compress_pgn_with_outcome(&moves, &outcome)
+        }
+        None => compress_pgn(&moves),
+    }
+}
+
+/// Decompresses a game into its movetext, ending with the result token that
+/// `wasm_compress_pgn` stored, if any.
+#[wasm_bindgen]
+pub fn wasm_decompress_pgn(compressed: &[u8], plies: usize) -> Result<String, WasmError> {
+    Ok(decompress_pgn_movetext(compressed, plies)?)
+}
+
+// Splits games stored back to back in one buffer into their bytes and plies
+fn batch_games<'a>(
+    data: &'a [u8],
+    offsets: &[u32],
+    plies: &[u32],
+) -> Result<Vec<(&'a [u8], usize)>, WasmError> {
+    if offsets.len() != plies.len() + 1 {
+        return Err(WasmError::new(
+            "INVALID_BATCH",
+            format!(
+                "Expected {} offsets for {} games, got {}",
+                plies.len() + 1,
+                plies.len(),
+                offsets.len()
+            ),
+        ));
+    }
+
+    let mut games = Vec::with_capacity(plies.len());
+    for (index, (bounds, &plies)) in offsets.windows(2).zip(plies).enumerate() {
+        let (start, end) = (bounds[0] as usize, bounds[1] as usize);
+        let game = data.get(start..end).ok_or_else(|| {
+            WasmError::new(
+                "INVALID_BATCH",
+                format!("Game bytes {}..{} are outside the buffer", start, end),
+            )
+            .with_index(index)
+        })?;
+        games.push((game, plies as usize));
+    }
+    Ok(games)
+}
+
+// Checks that a per-game array of a batch is empty or has one value per game
+fn check_batch_len(name: &str, len: usize, games: usize) -> Result<(), WasmError> {
+    if len != 0 && len != games {
+        return Err(WasmError::new(
+            "INVALID_BATCH",
+            format!("Expected {} {}, got {}", games, name, len),
+        ));
+    }
+    Ok(())
+}
+
+// Codec version of a `codec_version` column value, 1 for V1 and so on. Only
+// frozen versions are stored.
+fn codec_version_from_number(number: u8) -> Option<CodecVersion> {
+    match number {
+        1 => Some(CodecVersion::V1),
+        2 => Some(CodecVersion::V2),
+        3 => Some(CodecVersion::V3),
+        _ => None,
+    }
+}
+
+// Codec version of game `index` of a batch, V1 when none are given
+fn batch_codec_version(codec_versions: &[u8], index: usize) -> Result<CodecVersion, WasmError> {
+    let number = codec_versions.get(index).copied().unwrap_or(1);
+    codec_version_from_number(number).ok_or_else(|| {
+        WasmError::new("INVALID_BATCH", format!("Unknown codec version {}", number))
+            .with_index(index)
+    })
+}
+
+/// Strings returned by a batch function, as UTF-8 bytes back to back.
+///
+/// String `i` is `data[offsets[i]..offsets[i + 1]]`, laid out like the
+/// games passed to the batch functions, so JS decodes them without splitting
+/// on a separator.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StringBatch {
+    data: Vec<u8>,
+    offsets: Vec<u32>,
+}
+
+impl StringBatch {
+    fn with_capacity(strings: usize) -> Self {
+        let mut offsets = Vec::with_capacity(strings + 1);
+        offsets.push(0);
+        StringBatch {
+            data: Vec::new(),
+            offsets,
+        }
+    }
+
+    fn push(&mut self, string: &str) {
+        self.data.extend_from_slice(string.as_bytes());
+        self.offsets.push(self.data.len() as u32);
+    }
+}
+
+#[wasm_bindgen]
+impl StringBatch {
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn offsets(&self) -> Vec<u32> {
+        self.offsets.clone()
+    }
+
+    /// Number of strings in the batch.
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// Returns string `index`, or `undefined` past the end.
+    pub fn get(&self, index: usize) -> Option<String> {
+        let (start, end) = (*self.offsets.get(index)?, *self.offsets.get(index + 1)?);
+        // Only whole strings are pushed, so the bounds are character boundaries
+        core::str::from_utf8(&self.data[start as usize..end as usize])
+            .ok()
+            .map(String::from)
+    }
+}
+
+/// Decompresses many games stored back to back in one buffer.
+///
+/// Game `i` is `data[offsets[i]..offsets[i + 1]]` and has `plies[i]` moves,
+/// so `offsets` has one more entry than `plies`. `codec_versions` holds the
+/// `codec_version` column of each game, 1 for V1 and so on, or is empty when
+/// every game is V1.
+///
+/// Returns the movetext of each game as `wasm_decompress_pgn` does: the moves
+/// separated by spaces, followed by the stored result token, if any. A game
+/// that fails to decode fails the whole batch, with its index in the error.
+#[wasm_bindgen]
+pub fn wasm_decompress_pgn_batch(
+    data: &[u8],
+    offsets: &[u32],
+    plies: &[u32],
+    codec_versions: &[u8],
+) -> Result<StringBatch, WasmError> {
+    let games = batch_games(data, offsets, plies)?;
+    check_batch_len("codec versions", codec_versions.len(), games.len())?;
+    let mut output = StringBatch::with_capacity(games.len());
+    for (index, (game, plies)) in games.into_iter().enumerate() {
+        let version = batch_codec_version(codec_versions, index)?;
+        let movetext = decompress_pgn_movetext_with_version(game, plies, version)
+            .map_err(|e| WasmError::from(e).with_index(index))?;
+        output.push(&movetext);
+    }
+    Ok(output)
+}
+
+/// Decompresses consecutive 32-byte positions into FEN strings.
+#[wasm_bindgen]
+pub fn wasm_decompress_positions(data: &[u8]) -> Result<StringBatch, WasmError> {
+    if !data.len().is_multiple_of(32) {
+        return Err(WasmError::new(
+            "INVALID_LENGTH",
+            format!(
+                "Compressed positions must be 32 bytes each, got {} bytes",
+                data.len()
+            ),
+        ));
+    }
+    let mut fens = StringBatch::with_capacity(data.len() / 32);
+    for (index, chunk) in data.chunks_exact(32).enumerate() {
+        fens.push(&wasm_decompress_position(chunk).map_err(|e| e.with_index(index))?);
+    }
+    Ok(fens)
+}
+
+fn uci_moves(position: &Chess) -> Vec<String> {
+    position
+        .legal_moves()
+        .iter()
+        .map(|mv| UciMove::from_standard(mv).to_string())
+        .collect()
+}
+
+/// Legal moves of a FEN position in UCI notation.
+#[wasm_bindgen]
+pub fn wasm_legal_moves(fen: &str) -> Result<Vec<String>, WasmError> {
+    Ok(uci_moves(&parse_fen(fen)?))
+}
+
+/// Converts a SAN move of a FEN position to UCI notation.
+#[wasm_bindgen]
+pub fn wasm_san_to_uci(fen: &str, san: &str) -> Result<String, WasmError> {
+    let mv = parse_san(&parse_fen(fen)?, san)?;
+    Ok(UciMove::from_standard(&mv).to_string())
+}
+
+/// Converts a UCI move of a FEN position to SAN, with its check suffix.
+#[wasm_bindgen]
+pub fn wasm_uci_to_san(fen: &str, uci: &str) -> Result<String, WasmError> {
+    let mut position = parse_fen(fen)?;
+    let mv = parse_uci(&position, uci)?;
+    Ok(SanPlus::from_move_and_play_unchecked(&mut position, &mv).to_string())
+}
+
+/// Plays a move given in UCI or SAN notation and returns the FEN after it.
+#[wasm_bindgen]
+pub fn wasm_play(fen: &str, mv: &str) -> Result<String, WasmError> {
+    let mut position = parse_fen(fen)?;
+    let mv = match UciMove::from_ascii(mv.as_bytes()) {
+        Ok(_) => parse_uci(&position, mv)?,
+        Err(_) => parse_san(&position, mv)?,
+    };
+    position.play_unchecked(&mv);
+    Ok(to_fen(&position))
+}
+
+/// Status of a FEN position.
+///
+/// `history` holds the FENs of the earlier positions of the game, oldest
+/// first, to detect repetitions. It may be empty.
+#[wasm_bindgen]
+pub fn wasm_game_status(fen: &str, history: Vec<String>) -> Result<GameStatus, WasmError> {
+    let position = parse_fen(fen)?;
+    let history = history
+        .iter()
+        .enumerate()
+        .map(|(index, fen)| parse_fen(fen).map_err(|e| e.with_index(index)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(GameStatus::new(&position, &history))
+}
+
+/// Whether a position is in check or ends the game.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameStatus {
+    pub check: bool,
+    pub checkmate: bool,
+    pub stalemate: bool,
+    /// Neither side can checkmate.
+    pub insufficient_material: bool,
+    /// The position has occurred three times or more, counting this one.
+    pub threefold_repetition: bool,
+}
+
+impl GameStatus {
+    fn new(position: &Chess, history: &[Chess]) -> Self {
+        let key = |position: &Chess| position.zobrist_hash::<Zobrist64>(EnPassantMode::Legal);
+        let current = key(position);
+        let earlier = history.iter().filter(|p| key(p) == current).count();
+        GameStatus {
+            check: position.is_check(),
+            checkmate: position.is_checkmate(),
+            stalemate: position.is_stalemate(),
+            insufficient_material: position.is_insufficient_material(),
+            threefold_repetition: earlier >= 2,
+        }
+    }
+}
+
+/// A game read by `wasm_parse_pgn`.
+#[wasm_bindgen]
+pub struct ParsedGame {
+    tags: Vec<(String, String)>,
+    moves: Vec<String>,
+    compressed: Vec<u8>,
+    positions: Vec<u8>,
+    error: Option<WasmError>,
+}
+
+#[wasm_bindgen]
+impl ParsedGame {
+    /// Tag pairs as an object. A repeated tag keeps its first value.
+    #[wasm_bindgen(getter)]
+    pub fn headers(&self) -> js_sys::Object {
+        let headers = js_sys::Object::new();
+        for (key, value) in self.tags.iter().rev() {
+            // Setting a property on a plain object cannot fail
+            let _ = Reflect::set(&headers, &JsValue::from_str(key), &JsValue::from_str(value));
+        }
+        headers
+    }
+
+    /// SAN moves of the main line.
+    #[wasm_bindgen(getter)]
+    pub fn moves(&self) -> Vec<String> {
+        self.moves.clone()
+    }
+
+    /// Moves compressed with the default codec version.
+    #[wasm_bindgen(getter)]
+    pub fn compressed(&self) -> Vec<u8> {
+        self.compressed.clone()
+    }
+
+    /// Compressed position after each move, 32 bytes each.
+    #[wasm_bindgen(getter)]
+    pub fn positions(&self) -> Vec<u8> {
+        self.positions.clone()
+    }
+
+    /// The `ChessCompressionError` that rejected the game, or `null`.
+    #[wasm_bindgen(getter)]
+    pub fn error(&self) -> JsValue {
+        self.error.clone().map_or(JsValue::NULL, JsValue::from)
+    }
+}
+
+impl ParsedGame {
+    fn new(index: usize, game: Result<ImportedGame, ImportError>) -> Self {
+        let mut parsed = ParsedGame {
+            tags: Vec::new(),
+            moves: Vec::new(),
+            compressed: Vec::new(),
+            positions: Vec::new(),
+            error: None,
+        };
+        let game = match game {
+            Ok(game) => game,
+            Err(error) => {
+                parsed.error = Some(WasmError::from(error).with_index(index));
+                return parsed;
+            }
+        };
+        match transcode_moves(&game.moves, &TranscodeOptions::new()) {
+            Ok(transcoded) => {
+                parsed.compressed = transcoded.compressed;
+                parsed.positions = transcoded
+                    .positions
+                    .iter()
+                    .flat_map(|sampled| sampled.position)
+                    .collect();
+            }
+            Err(error) => parsed.error = Some(WasmError::from(error).with_index(index)),
+        }
+        parsed.tags = game.tags;
+        parsed.moves = game.moves;
+        parsed
+    }
+}
+
+/// Reads every game of a PGN text, the same way the database importer does.
+///
+/// Rejected games are kept, with their index in the text, so callers can
+/// report them.
+#[wasm_bindgen]
+pub fn wasm_parse_pgn(pgn: &str) -> Vec<ParsedGame> {
+    import_games(pgn.as_bytes())
+        .into_iter()
+        .enumerate()
+        .map(|(index, game)| ParsedGame::new(index, game))
+        .collect()
+}
+
+/// Game counts and results of the games that reached a position or played a
+/// move.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExplorerCounts {
+    pub games: u32,
+    pub white: u32,
+    pub draws: u32,
+    pub black: u32,
+    /// Average rating of the rated games, or `undefined` if none is rated.
+    pub average_elo: Option<i32>,
+}
+
+impl From<ExplorerStats> for ExplorerCounts {
+    fn from(stats: ExplorerStats) -> Self {
+        ExplorerCounts {
+            games: stats.games,
+            white: stats.white,
+            draws: stats.draws,
+            black: stats.black,
+            average_elo: stats.average_elo(),
+        }
+    }
+}
+
+/// A move played from the explored position.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExplorerMoveCounts {
+    pub uci: String,
+    pub san: String,
+    pub counts: ExplorerCounts,
+}
+
+/// The explored position and the moves played from it, most played first.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExplorerResult {
+    /// Every game that reached the position, including games that ended there.
+    pub counts: ExplorerCounts,
+    pub moves: Vec<ExplorerMoveCounts>,
+}
+
+/// Computes opening explorer statistics for a position from compressed games,
+/// such as a player's imported games, without a database.
+///
+/// Games are laid out as for `wasm_decompress_pgn_batch`. The other arrays
+/// hold one value per game, or are empty when no game has one:
+///
+/// - `white_elos` and `black_elos`: ratings, 0 when unknown.
+/// - `results`: 1 when White won, 2 when Black won, 3 for a draw and 0 when
+///   unknown, for games without an outcome trailer, such as those of the
+///   games table. A trailer, which `wasm_compress_pgn` stores when the moves
+///   end with a result token, takes precedence.
+/// - `codec_versions`: the `codec_version` column, 1 for V1 and so on. Games
+///   are V1 when it is empty.
 #[wasm_bindgen]
-pub fn wasm_decompress_pgn(compressed: &[u8], plies: usize) -> Result<String, JsValue> {
-    let moves = decompress_pgn(compressed, plies).map_err(|e| JsValue::from_str(&e.to_string()))?;
-    Ok(moves.join(" "))
+#[allow(clippy::too_many_arguments)]
+pub fn wasm_explore(
+    fen: &str,
+    data: &[u8],
+    offsets: &[u32],
+    plies: &[u32],
+    white_elos: &[i32],
+    black_elos: &[i32],
+    results: &[u8],
+    codec_versions: &[u8],
+) -> Result<ExplorerResult, WasmError> {
+    let position = parse_fen(fen)?;
+    let games = batch_games(data, offsets, plies)?;
+    let lengths = [
+        ("ratings", white_elos.len()),
+        ("ratings", black_elos.len()),
+        ("results", results.len()),
+        ("codec versions", codec_versions.len()),
+    ];
+    for (name, len) in lengths {
+        check_batch_len(name, len, games.len())?;
+    }
+
+    let elo = |elos: &[i32], index: usize| elos.get(index).copied().filter(|&elo| elo > 0);
+    let mut explorer = Explorer::new(position);
+    for (index, (compressed, plies)) in games.into_iter().enumerate() {
+        let result = match results.get(index).copied().unwrap_or(0) {
+            0 => GameResult::Unknown,
+            1 => GameResult::WhiteWins,
+            2 => GameResult::BlackWins,
+            3 => GameResult::Draw,
+            code => {
+                return Err(WasmError::new(
+                    "INVALID_BATCH",
+                    format!("Unknown result code {}", code),
+                )
+                .with_index(index))
+            }
+        };
+        let game = ExplorerGame {
+            compressed,
+            plies,
+            version: batch_codec_version(codec_versions, index)?,
+            result,
+            white_elo: elo(white_elos, index),
+            black_elo: elo(black_elos, index),
+        };
+        explorer
+            .add_game(&game)
+            .map_err(|e| WasmError::from(e).with_index(index))?;
+    }
+
+    let tree = explorer.finish();
+    Ok(ExplorerResult {
+        counts: tree.stats.into(),
+        moves: tree
+            .moves
+            .into_iter()
+            .map(|mv| ExplorerMoveCounts {
+                uci: mv.uci,
+                san: mv.san,
+                counts: mv.stats.into(),
+            })
+            .collect(),
+    })
+}
+
+/// A game replayed move by move, for interactive boards.
+///
+/// The cursor holds a line of moves and a current ply within it, starting at
+/// the initial position. Playing a move other than the next one in the line
+/// replaces the rest of the line with it.
+#[wasm_bindgen]
+pub struct GameCursor {
+    // Position after each number of moves, so `positions[ply]` is current
+    positions: Vec<Chess>,
+    moves: Vec<Move>,
+    sans: Vec<String>,
+    ply: usize,
+}
+
+#[wasm_bindgen]
+impl GameCursor {
+    /// Replays a game compressed with the default codec version.
+    pub fn from_compressed(compressed: &[u8], plies: usize) -> Result<GameCursor, WasmError> {
+        Self::from_compressed_with_version(compressed, plies, 1)
+    }
+
+    /// Replays a game compressed with the given codec version, the
+    /// `codec_version` column of its row: 1 for V1 and so on.
+    pub fn from_compressed_with_version(
+        compressed: &[u8],
+        plies: usize,
+        codec_version: u8,
+    ) -> Result<GameCursor, WasmError> {
+        let version = codec_version_from_number(codec_version).ok_or_else(|| {
+            WasmError::new(
+                "INVALID_CODEC_VERSION",
+                format!("Unknown codec version {}", codec_version),
+            )
+        })?;
+        let data = BitVec::from_bytes(compressed);
+        let mut cursor = GameCursor::new();
+        for mv in Decoder::with_version(version).decode_iter(&data, plies) {
+            cursor.push(mv?);
+        }
+        Ok(cursor)
+    }
+
+    /// Replays space-separated SAN moves. A trailing result token is ignored.
+    pub fn from_moves(moves: &str) -> Result<GameCursor, WasmError> {
+        let mut moves: Vec<&str> = moves.split_whitespace().collect();
+        if moves
+            .last()
+            .is_some_and(|token| token.parse::<GameResult>().is_ok())
+        {
+            moves.pop();
+        }
+        let mut cursor = GameCursor::new();
+        for san in moves {
+            let position = &cursor.positions[cursor.moves.len()];
+            let mv = san_to_move(position, cursor.moves.len(), san)?;
+            cursor.push(mv);
+        }
+        Ok(cursor)
+    }
+
+    /// Number of moves played to reach the current position.
+    #[wasm_bindgen(getter)]
+    pub fn ply(&self) -> usize {
+        self.ply
+    }
+
+    /// Number of moves in the line.
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.moves.len()
+    }
+
+    /// Moves forward one ply. Returns `false` at the end of the line.
+    #[wasm_bindgen(js_name = next)]
+    pub fn next_ply(&mut self) -> bool {
+        let more = self.ply < self.moves.len();
+        if more {
+            self.ply += 1;
+        }
+        more
+    }
+
+    /// Moves back one ply. Returns `false` at the initial position.
+    pub fn prev(&mut self) -> bool {
+        let more = self.ply > 0;
+        if more {
+            self.ply -= 1;
+        }
+        more
+    }
+
+    /// Jumps to the position after `ply` moves of the line.
+    pub fn goto(&mut self, ply: usize) -> Result<(), WasmError> {
+        if ply > self.moves.len() {
+            return Err(WasmError::new(
+                "INVALID_PLY",
+                format!(
+                    "Ply {} is past the end of a {} ply line",
+                    ply,
+                    self.moves.len()
+                ),
+            ));
+        }
+        self.ply = ply;
+        Ok(())
+    }
+
+    /// FEN of the current position.
+    pub fn fen(&self) -> String {
+        to_fen(self.position())
+    }
+
+    /// Legal moves of the current position in UCI notation.
+    pub fn legal_moves(&self) -> Vec<String> {
+        uci_moves(self.position())
+    }
+
+    /// SAN moves leading to the current position.
+    pub fn san_history(&self) -> Vec<String> {
+        self.sans[..self.ply].to_vec()
+    }
+
+    /// Plays a UCI move from the current position and returns its SAN.
+    ///
+    /// If the move is the next one in the line, this is the same as `next`.
+    /// Otherwise the rest of the line is replaced by the move.
+    pub fn play(&mut self, uci: &str) -> Result<String, WasmError> {
+        let mv = parse_uci(self.position(), uci)?;
+
+        if self.moves.get(self.ply) != Some(&mv) {
+            self.moves.truncate(self.ply);
+            self.sans.truncate(self.ply);
+            self.positions.truncate(self.ply + 1);
+            self.push(mv);
+        }
+        self.ply += 1;
+        Ok(self.sans[self.ply - 1].clone())
+    }
+
+    /// Status of the current position, counting repetitions earlier in the line.
+    pub fn status(&self) -> GameStatus {
+        GameStatus::new(self.position(), &self.positions[..self.ply])
+    }
+}
+
+impl GameCursor {
+    fn new() -> Self {
+        GameCursor {
+            positions: vec![Chess::default()],
+            moves: Vec::new(),
+            sans: Vec::new(),
+            ply: 0,
+        }
+    }
+
+    fn position(&self) -> &Chess {
+        &self.positions[self.ply]
+    }
+
+    // Appends a legal move of the last position to the line
+    fn push(&mut self, mv: Move) {
+        let mut position = self.positions[self.moves.len()].clone();
+        let san = SanPlus::from_move_and_play_unchecked(&mut position, &mv);
+        self.positions.push(position);
+        self.sans.push(san.to_string());
+        self.moves.push(mv);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compress_pgn_with_version, decompress_pgn};
+
+    #[test]
+    fn test_invalid_inputs_return_errors() {
+        let error = wasm_decompress_position(&[0; 31]).unwrap_err();
+        assert_eq!(error.code, "INVALID_LENGTH");
+
+        let error = wasm_evaluate("not a fen").unwrap_err();
+        assert_eq!(error.code, "INVALID_FEN");
+        assert_eq!(error.fen.as_deref(), Some("not a fen"));
+
+        let error = wasm_evaluate("8/8/8/8/8/8/8/8 w - - 0 1").unwrap_err();
+        assert_eq!(error.code, "INVALID_POSITION");
+    }
+
+    #[test]
+    fn test_decode_errors_carry_context() {
+        // Far more plies than the data holds
+        let error = wasm_decompress_pgn(&[0xff], 40).unwrap_err();
+        assert_eq!(error.code, "INVALID_MOVE_INDEX");
+        assert!(error.ply.is_some());
+        assert!(error.fen.is_some());
+        assert_eq!(error.san, None);
+    }
+
+    #[test]
+    fn test_pgn_roundtrip_keeps_result() -> Result<(), WasmError> {
+        let movetext = "e4 e5 Qh5 Nc6 Bc4 Nf6 Qxf7# 1-0";
+        let compressed = compress_movetext(movetext)?;
+        assert_eq!(wasm_decompress_pgn(&compressed, 7)?, movetext);
+
+        let compressed = compress_movetext("d4 d5")?;
+        assert_eq!(wasm_decompress_pgn(&compressed, 2)?, "d4 d5");
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_decompression() -> Result<(), WasmError> {
+        let games = ["e4 c5 Nf3", "", "d4 d5"];
+        let (mut data, mut offsets, mut plies) = (Vec::new(), vec![0], Vec::new());
+        for game in games {
+            let game: Vec<String> = game.split_whitespace().map(String::from).collect();
+            data.extend(compress_pgn(&game)?);
+            offsets.push(data.len() as u32);
+            plies.push(game.len() as u32);
+        }
+        let batch = wasm_decompress_pgn_batch(&data, &offsets, &plies, &[])?;
+        let strings: Vec<_> = (0..batch.length()).filter_map(|i| batch.get(i)).collect();
+        assert_eq!(strings, games);
+        assert_eq!(batch.offsets(), [0, 9, 9, 14]);
+
+        let error = wasm_decompress_pgn_batch(&data, &offsets[..3], &plies, &[]).unwrap_err();
+        assert_eq!(error.code, "INVALID_BATCH");
+        let error = wasm_decompress_pgn_batch(&data, &offsets, &plies, &[1]).unwrap_err();
+        assert_eq!(error.code, "INVALID_BATCH");
+        // Asking the last game for moves it does not have
+        plies[2] = 30;
+        let error = wasm_decompress_pgn_batch(&data, &offsets, &plies, &[]).unwrap_err();
+        assert_eq!(error.index, Some(2));
+
+        // Each game decodes with its own version, and ends with its stored result
+        let moves: Vec<String> = ["f3", "e5", "g4", "Qh4#"].map(String::from).into();
+        let v1 = compress_movetext("f3 e5 g4 Qh4# 0-1")?;
+        let v3 = compress_pgn_with_version(&moves, CodecVersion::V3)?;
+        let data = [v1.as_slice(), v3.as_slice()].concat();
+        let offsets = [0, v1.len() as u32, data.len() as u32];
+        let batch = wasm_decompress_pgn_batch(&data, &offsets, &[4, 4], &[1, 3])?;
+        assert_eq!(batch.get(0).as_deref(), Some("f3 e5 g4 Qh4# 0-1"));
+        assert_eq!(batch.get(1).as_deref(), Some("f3 e5 g4 Qh4#"));
+
+        let start = compress_position(&Chess::default());
+        let positions = [start.as_slice(), start.as_slice()].concat();
+        let fens = wasm_decompress_positions(&positions)?;
+        assert_eq!(fens.length(), 2);
+        assert_eq!(fens.get(1), Some(to_fen(&Chess::default())));
+        assert_eq!(fens.get(2), None);
+        let error = wasm_decompress_positions(&positions[1..]).unwrap_err();
+        assert_eq!(error.code, "INVALID_LENGTH");
+        Ok(())
+    }
+
+    #[test]
+    fn test_game_cursor_navigation() -> Result<(), WasmError> {
+        let mut cursor = GameCursor::from_moves("e4 e5 Nf3 Nc6 Bb5 1-0")?;
+        assert_eq!((cursor.ply(), cursor.length()), (0, 5));
+        assert_eq!(cursor.legal_moves().len(), 20);
+        assert!(!cursor.prev());
+
+        assert!(cursor.next_ply());
+        assert_eq!(cursor.san_history(), ["e4"]);
+        cursor.goto(5)?;
+        assert!(!cursor.next_ply());
+        assert_eq!(
+            cursor.fen(),
+            "r1bqkbnr/pppp1ppp/2n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 3 3"
+        );
+        assert_eq!(cursor.goto(6).unwrap_err().code, "INVALID_PLY");
+
+        let compressed = compress_pgn(&cursor.san_history())?;
+        let replayed = GameCursor::from_compressed(&compressed, 5)?;
+        assert_eq!(replayed.sans, cursor.sans);
+
+        let compressed = compress_pgn_with_version(&cursor.san_history(), CodecVersion::V3)?;
+        let replayed = GameCursor::from_compressed_with_version(&compressed, 5, 3)?;
+        assert_eq!(replayed.sans, cursor.sans);
+        let error = GameCursor::from_compressed_with_version(&compressed, 5, 9).err();
+        assert_eq!(error.map(|e| e.code), Some("INVALID_CODEC_VERSION"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_game_cursor_play_branches() -> Result<(), WasmError> {
+        let mut cursor = GameCursor::from_moves("e4 e5 Nf3 Nc6")?;
+        cursor.goto(2)?;
+
+        // Following the line keeps it
+        assert_eq!(cursor.play("g1f3")?, "Nf3");
+        assert_eq!(cursor.length(), 4);
+
+        // Deviating replaces the rest of it
+        assert_eq!(cursor.play("d7d6")?, "d6");
+        assert_eq!(cursor.length(), 4);
+        assert_eq!(cursor.play("d2d4")?, "d4");
+        assert_eq!(cursor.san_history(), ["e4", "e5", "Nf3", "d6", "d4"]);
+
+        assert_eq!(cursor.play("e1e3").unwrap_err().code, "ILLEGAL_MOVE");
+        assert_eq!(cursor.play("nonsense").unwrap_err().code, "INVALID_UCI");
+        assert_eq!(cursor.ply(), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_move_conversion_and_play() -> Result<(), WasmError> {
+        let start = to_fen(&Chess::default());
+        assert_eq!(wasm_legal_moves(&start)?.len(), 20);
+        assert_eq!(wasm_san_to_uci(&start, "Nf3")?, "g1f3");
+        assert_eq!(wasm_uci_to_san(&start, "e2e4")?, "e4");
+
+        let castling = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(wasm_uci_to_san(castling, "e1g1")?, "O-O");
+        assert_eq!(wasm_san_to_uci(castling, "O-O-O")?, "e1c1");
+
+        let after = wasm_play(&start, "e2e4")?;
+        assert_eq!(wasm_play(&start, "e4")?, after);
+        assert_eq!(
+            after,
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"
+        );
+
+        let error = wasm_play(&start, "e5").unwrap_err();
+        assert_eq!(error.code, "ILLEGAL_MOVE");
+        assert_eq!(error.san.as_deref(), Some("e5"));
+        assert_eq!(error.fen.as_deref(), Some(start.as_str()));
+        assert_eq!(
+            wasm_san_to_uci(&start, "Zz9").unwrap_err().code,
+            "SAN_PARSE"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_game_status() -> Result<(), WasmError> {
+        let mate = "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3";
+        let status = wasm_game_status(mate, Vec::new())?;
+        assert!(status.check && status.checkmate && !status.stalemate);
+
+        let stalemate = wasm_game_status("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", Vec::new())?;
+        assert!(stalemate.stalemate && !stalemate.check);
+
+        let bare_kings = wasm_game_status("8/8/4k3/8/8/3K4/8/8 w - - 0 1", Vec::new())?;
+        assert!(bare_kings.insufficient_material);
+
+        // The knights go out and back twice
+        let mut cursor = GameCursor::from_moves("Nf3 Nf6 Ng1 Ng8 Nf3 Nf6 Ng1 Ng8")?;
+        cursor.goto(4)?;
+        assert!(!cursor.status().threefold_repetition);
+        cursor.goto(8)?;
+        assert!(cursor.status().threefold_repetition);
+
+        let history: Vec<String> = (0..8)
+            .map(|ply| {
+                cursor.goto(ply).unwrap();
+                cursor.fen()
+            })
+            .collect();
+        let start = to_fen(&Chess::default());
+        assert!(wasm_game_status(&start, history)?.threefold_repetition);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_pgn() {
+        let pgn = "[White \"A\"]\n[Black \"B\"]\n[Result \"*\"]\n\n1. e4 *\n\n\
+                   [White \"A\"]\n[Black \"B\"]\n[Result \"1-0\"]\n\n1. e4 e5 2. Ke3 1-0\n\n\
+                   [Event \"Open\"]\n[White \"A\"]\n[Black \"B\"]\n[Result \"0-1\"]\n\n\
+                   1. f3 e5 2. g4 Qh4# 0-1\n";
+        let games = wasm_parse_pgn(pgn);
+        assert_eq!(games.len(), 3);
+
+        let error = games[0].error.as_ref().unwrap();
+        assert_eq!((error.code, error.index), ("UNFINISHED_GAME", Some(0)));
+
+        // Ke3 is illegal, so the moves are kept but not compressed
+        let error = games[1].error.as_ref().unwrap();
+        assert_eq!((error.code, error.ply), ("ILLEGAL_MOVE", Some(2)));
+        assert!(games[1].compressed.is_empty());
+
+        let game = &games[2];
+        assert!(game.error.is_none());
+        assert_eq!(game.tags[0], ("Event".to_string(), "Open".to_string()));
+        assert_eq!(game.moves, ["f3", "e5", "g4", "Qh4#"]);
+        assert_eq!(decompress_pgn(&game.compressed, 4).unwrap(), game.moves);
+        assert_eq!(game.positions.len(), 4 * 32);
+    }
+
+    #[test]
+    fn test_explore() -> Result<(), WasmError> {
+        let games = [
+            "e4 e5 Nf3 1-0",
+            "e4 c5 0-1",
+            "e4 e5 Bc4 1/2-1/2",
+            "d4 d5 1-0",
+        ];
+        let (mut data, mut offsets, mut plies) = (Vec::new(), vec![0], Vec::new());
+        for game in games {
+            let moves: Vec<String> = game.split_whitespace().map(String::from).collect();
+            let outcome = GameOutcome::new(
+                moves[moves.len() - 1].parse().unwrap(),
+                Termination::Unknown,
+            );
+            data.extend(compress_pgn_with_outcome(&moves[..moves.len() - 1], &outcome).unwrap());
+            offsets.push(data.len() as u32);
+            plies.push(moves.len() as u32 - 1);
+        }
+
+        let after_e4 = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+        let result = wasm_explore(
+            after_e4,
+            &data,
+            &offsets,
+            &plies,
+            &[2000, 0, 1800, 2500],
+            &[],
+            &[],
+            &[],
+        )?;
+        assert_eq!((result.counts.games, result.counts.white), (3, 1));
+        assert_eq!(result.counts.average_elo, Some(1900));
+        let sans: Vec<_> = result.moves.iter().map(|mv| mv.san.as_str()).collect();
+        assert_eq!(sans, ["e5", "c5"]);
+        assert_eq!(result.moves[0].counts.draws, 1);
+
+        let error =
+            wasm_explore(after_e4, &data, &offsets, &plies, &[2000], &[], &[], &[]).unwrap_err();
+        assert_eq!(error.code, "INVALID_BATCH");
+
+        // Games as the importer stores them: results in a column, no trailer
+        let moves: Vec<String> = ["e4", "e5", "Nf3"].map(String::from).into();
+        let v1 = compress_pgn(&moves)?;
+        let v2 = compress_pgn_with_version(&moves, CodecVersion::V2)?;
+        let data = [v1.as_slice(), v2.as_slice()].concat();
+        let offsets = [0, v1.len() as u32, data.len() as u32];
+        let result = wasm_explore(
+            after_e4,
+            &data,
+            &offsets,
+            &[3, 3],
+            &[],
+            &[],
+            &[1, 3],
+            &[1, 2],
+        )?;
+        assert_eq!(
+            (
+                result.counts.games,
+                result.counts.white,
+                result.counts.draws
+            ),
+            (2, 1, 1)
+        );
+        let error = wasm_explore(
+            after_e4,
+            &data,
+            &offsets,
+            &[3, 3],
+            &[],
+            &[],
+            &[1, 3],
+            &[1, 9],
+        )
+        .unwrap_err();
+        assert_eq!((error.code, error.index), ("INVALID_BATCH", Some(1)));
+        Ok(())
+    }
+}
+
+// Run by `npm test` in chess-compression-wasm, through the JS types
+#[cfg(all(test, target_arch = "wasm32"))]
+mod wasm_tests {
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test]
+    fn test_pgn_roundtrip_through_js() {
+        let movetext = "e4 e5 Qh5 Nc6 Bc4 Nf6 Qxf7# 1-0";
+        let compressed = wasm_compress_pgn(movetext).unwrap().to_vec();
+        assert_eq!(wasm_decompress_pgn(&compressed, 7).unwrap(), movetext);
+
+        let position = wasm_compress_position(&to_fen(&Chess::default()))
+            .unwrap()
+            .to_vec();
+        assert_eq!(
+            wasm_decompress_position(&position).unwrap(),
+            to_fen(&Chess::default())
+        );
+    }
 }
@@ -0,0 +1,5 @@
+-- === Down Migrations ===
+
+DROP INDEX IF EXISTS idx_games_fingerprint;
+
+ALTER TABLE games DROP COLUMN IF EXISTS fingerprint;
@@ -0,0 +1,8 @@
+-- === Up Migrations ===
+
+-- Stable 128-bit fingerprint of the moves, players, date and result, used to
+-- skip games that were already imported from an overlapping source
+ALTER TABLE games
+    ADD COLUMN fingerprint BYTEA CHECK (octet_length(fingerprint) = 16);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_games_fingerprint ON games(fingerprint);
@@ -0,0 +1,3 @@
+-- === Down Migrations ===
+
+ALTER TABLE games DROP COLUMN IF EXISTS plies;
@@ -0,0 +1,6 @@
+-- === Up Migrations ===
+
+-- Number of moves in pgn_moves, which the compressed moves do not record.
+-- Games imported before this column was added have no count.
+ALTER TABLE games
+    ADD COLUMN plies SMALLINT CHECK (plies >= 0);
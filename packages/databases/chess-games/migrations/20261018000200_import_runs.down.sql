@@ -0,0 +1,5 @@
+-- === Down Migrations ===
+
+DROP INDEX IF EXISTS idx_import_runs_checksum_offset;
+
+DROP TABLE IF EXISTS import_runs;
@@ -0,0 +1,18 @@
+-- === Up Migrations ===
+
+-- One row per committed import batch, written in the batch's transaction.
+-- byte_offset is where the next game of the file starts, so an interrupted
+-- import resumes from the largest offset recorded for the file's checksum.
+CREATE TABLE import_runs (
+    id SERIAL PRIMARY KEY,
+    source_file VARCHAR NOT NULL,
+    checksum BYTEA NOT NULL CHECK (octet_length(checksum) = 32),
+    byte_offset BIGINT NOT NULL CHECK (byte_offset >= 0),
+    games INTEGER NOT NULL CHECK (games >= 0),
+    games_inserted INTEGER NOT NULL CHECK (games_inserted >= 0),
+    committed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Two imports of the same file cannot both commit the same batch
+CREATE UNIQUE INDEX IF NOT EXISTS idx_import_runs_checksum_offset
+    ON import_runs(checksum, byte_offset);
@@ -0,0 +1,10 @@
+-- === Up Migrations ===
+
+-- Codec version pgn_moves is encoded with, which the compressed moves do not
+-- record. Every game stored so far was imported with version 1. The default
+-- only fills existing rows, so writers must always set the version.
+ALTER TABLE games
+    ADD COLUMN codec_version SMALLINT NOT NULL DEFAULT 1 CHECK (codec_version >= 1);
+
+ALTER TABLE games
+    ALTER COLUMN codec_version DROP DEFAULT;